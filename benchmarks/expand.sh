@@ -0,0 +1,68 @@
+#!/usr/bin/sh
+
+# Measures how long it takes to load template with large file. The file in
+# the first template contains only text and the file in the second template
+# contains many expressions.
+#
+# Usage: expand.sh [OTHER_MAKEMAKE]
+#
+# If path to other build of makemake is given (e.g. older version), it is
+# measured on the same templates so that the times can be compared. The
+# templates use only syntax that is supported by older versions.
+
+cd "$(dirname "$0")"
+
+cd ..
+cargo build -r
+cd -
+
+makemake=$(realpath ../target/release/makemake)
+other=${1:+$(realpath "$1")}
+dir=$(mktemp -d)
+
+line='Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod.'
+for i in $(seq 16); do
+    line="$line
+$line"
+done
+
+# 64 Ki lines, ~5 MB
+for t in text exprs; do
+    mkdir "$dir/$t"
+    cat > "$dir/$t/makemake.json" <<'JSON'
+{
+    "files": {
+        "makemake.json": "Ignore",
+        "file": "Make"
+    },
+    "vars": {
+        "name": "world"
+    }
+}
+JSON
+done
+printf '%s\n' "$line" > "$dir/text/file"
+printf '%s\n' "$line" \
+    | sed "s/dolor/\${name}/;s/elit/\${name ? '[' name ']' : _}/" \
+    > "$dir/exprs/file"
+
+# Prints the time of loading each template with the given makemake.
+measure() {
+    for t in text exprs; do
+        printf '%-6s' "$t:"
+        rm -rf "$dir/out-$t"
+        start=$(date +%s%N)
+        "$1" --load-to "$dir/$t" "$dir/out-$t" >/dev/null
+        end=$(date +%s%N)
+        echo " $(( (end - start) / 1000000 )) ms"
+    done
+}
+
+if [ -n "$other" ]; then
+    echo "$other"
+    measure "$other"
+    echo "$makemake"
+fi
+measure "$makemake"
+
+rm -r "$dir"
@@ -0,0 +1,20 @@
+#!/usr/bin/sh
+
+cd "$(dirname "$0")"
+
+makemake=../../target/release/makemake
+
+$makemake -py -c test -d template
+$makemake test -d res -Dport=8080
+$makemake test -d res-err -Dport=8080 -Dn=seven 2> res-err-msg
+$makemake -r test
+
+if diff expected res/file && diff expected-err res-err-msg; then
+    echo success
+    rm -r res res-err res-err-msg
+    exit 0
+else
+    echo failure
+    rm -r res res-err res-err-msg
+    exit 1
+fi
@@ -0,0 +1,24 @@
+#!/usr/bin/sh
+
+cd "$(dirname "$0")"
+
+makemake=../../target/release/makemake
+
+$makemake -py -c test -d template
+$makemake test -d res --explain > res-explain
+$makemake -r test
+
+if diff expected res/file \
+    && diff expected-explain res-explain \
+    && diff template/script.sh res/script.sh \
+    && diff template/plain res/plain \
+    && cmp template/data.bin res/data.bin
+then
+    echo success
+    rm -r res res-explain
+    exit 0
+else
+    echo failure
+    rm -r res res-explain
+    exit 1
+fi
@@ -0,0 +1,22 @@
+#!/usr/bin/sh
+
+cd "$(dirname "$0")"
+
+makemake=../../target/release/makemake
+
+$makemake -py -c test -d template
+$makemake test -d res --explain -Ddocker=alpine > res-explain
+$makemake -r test
+
+if diff expected res/ci.yml \
+    && diff expected-explain res-explain \
+    && ! [ -e res/nix ]
+then
+    echo success
+    rm -r res res-explain
+    exit 0
+else
+    echo failure
+    rm -r res res-explain
+    exit 1
+fi
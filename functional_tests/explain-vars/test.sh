@@ -0,0 +1,22 @@
+#!/usr/bin/sh
+
+cd "$(dirname "$0")"
+
+makemake=../../target/release/makemake
+
+$makemake -py -c test -d template
+# internal variables depend on the platform
+$makemake test -d res -Dname=cli -Ulicense \
+    --vars-file vars.json --explain-vars \
+    | grep -v '\[internal\]' > res-explain
+$makemake -r test
+
+if diff expected res/file && diff expected-explain res-explain; then
+    echo success
+    rm -r res res-explain
+    exit 0
+else
+    echo failure
+    rm -r res res-explain
+    exit 1
+fi
@@ -0,0 +1,2 @@
+// ${#make("common.txt")}
+port=${port} debug=${debug ?? 'no'}
@@ -0,0 +1,24 @@
+#!/usr/bin/sh
+
+cd "$(dirname "$0")"
+
+makemake=../../target/release/makemake
+
+$makemake -py -c test -d template
+$makemake test -d res
+$makemake test -d res-cli -Drole=cli -Uport
+$makemake -r test
+
+if diff expected-server res/server/main.rs \
+    && diff expected-client res/client/main.rs \
+    && diff expected-cli-server res-cli/server/main.rs \
+    && diff expected-cli-client res-cli/client/main.rs
+then
+    echo success
+    rm -r res res-cli
+    exit 0
+else
+    echo failure
+    rm -r res res-cli
+    exit 1
+fi
@@ -0,0 +1,24 @@
+#!/usr/bin/sh
+
+cd "$(dirname "$0")"
+
+makemake=../../target/release/makemake
+
+$makemake -py -c test -d template
+$makemake test -d res --explain > res-explain
+$makemake -r test
+
+if diff expected res/src/deep/er/b.txt \
+    && diff expected 'res/pages/[id].js' \
+    && diff expected-explain res-explain \
+    && diff template/src/raw.txt res/src/raw.txt \
+    && ! [ -e res/src/deep/y.skip ]
+then
+    echo success
+    rm -r res res-explain
+    exit 0
+else
+    echo failure
+    rm -r res res-explain
+    exit 1
+fi
@@ -0,0 +1,38 @@
+#!/usr/bin/sh
+
+cd "$(dirname "$0")"
+
+makemake=../../target/release/makemake
+
+$makemake -py -c test -d template
+$makemake test -d res-loop -Dloop 2> res-loop-err
+$makemake test -d res-big -Dbig 2> res-big-err
+$makemake test -d res-nested -Dnested 2> res-nested-err
+$makemake test -d res-indent -Dalloc -Dindent 2> res-indent-err
+$makemake test -d res-random -Dalloc 2> res-random-err
+$makemake test -d res-steps -Dsteps 2> res-steps-err
+$makemake -r test
+
+if diff expected-loop res-loop-err \
+    && diff expected-big res-big-err \
+    && diff expected-nested res-nested-err \
+    && diff expected-big res-indent-err \
+    && diff expected-big res-random-err \
+    && diff expected-steps res-steps-err \
+    && ! [ -e res-loop/loop ] \
+    && ! [ -e res-big/big ] \
+    && ! [ -e res-nested/nested ] \
+    && ! [ -e res-steps/steps ]
+then
+    echo success
+    rm -r res-loop res-loop-err res-big res-big-err res-nested res-nested-err \
+        res-indent res-indent-err res-random res-random-err res-steps \
+        res-steps-err
+    exit 0
+else
+    echo failure
+    rm -r res-loop res-loop-err res-big res-big-err res-nested res-nested-err \
+        res-indent res-indent-err res-random res-random-err res-steps \
+        res-steps-err
+    exit 1
+fi
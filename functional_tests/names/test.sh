@@ -0,0 +1,21 @@
+#!/usr/bin/sh
+
+cd "$(dirname "$0")"
+
+makemake=../../target/release/makemake
+
+$makemake -py -c test -d template
+$makemake test -d res -Dreadme
+$makemake -r test
+
+if diff expected res/src/hello/hello.rs \
+    && [ "$(ls res)" = "$(printf 'README.md\nsrc')" ]
+then
+    echo success
+    rm -r res
+    exit 0
+else
+    echo failure
+    rm -r res
+    exit 1
+fi
@@ -0,0 +1 @@
+package com.${group}.app;
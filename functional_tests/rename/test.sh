@@ -0,0 +1,22 @@
+#!/usr/bin/sh
+
+cd "$(dirname "$0")"
+
+makemake=../../target/release/makemake
+
+$makemake -py -c test -d template
+$makemake test -d res
+$makemake -r test
+
+if diff expected res/com/example/app/Main.java \
+    && diff template/docs/index.md res/share/doc/hello/index.md \
+    && diff template/run.sh res/hello.sh
+then
+    echo success
+    rm -r res
+    exit 0
+else
+    echo failure
+    rm -r res
+    exit 1
+fi
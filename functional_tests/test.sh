@@ -14,3 +14,47 @@ printf 'call       : '
 ./call/test.sh
 printf 'rule 110:  : '
 ./rule110/test.sh
+printf 'var deps   : '
+./var-deps/test.sh
+printf 'structured : '
+./structured/test.sh
+printf 'switch     : '
+./switch/test.sh
+printf 'arithmetic : '
+./arithmetic/test.sh
+printf 'semver     : '
+./semver/test.sh
+printf 'escape     : '
+./escape/test.sh
+printf 'paths      : '
+./paths/test.sh
+printf 'text       : '
+./text/test.sh
+printf 'random     : '
+./random/test.sh
+printf 'hash       : '
+./hash/test.sh
+printf 'literals   : '
+./literals/test.sh
+printf 'escape seq : '
+./escape-seq/test.sh
+printf 'memo       : '
+./memo/test.sh
+printf 'bytes      : '
+./bytes/test.sh
+printf 'auto       : '
+./auto/test.sh
+printf 'glob       : '
+./glob/test.sh
+printf 'names      : '
+./names/test.sh
+printf 'rename     : '
+./rename/test.sh
+printf 'condition  : '
+./condition/test.sh
+printf 'file vars  : '
+./file-vars/test.sh
+printf 'limits     : '
+./limits/test.sh
+printf 'expl vars  : '
+./explain-vars/test.sh
@@ -0,0 +1,20 @@
+#!/usr/bin/sh
+
+cd "$(dirname "$0")"
+
+makemake=../../target/release/makemake
+
+$makemake -py -c test -d template
+$makemake test -d res -Dname=my_app
+$makemake test -d res-undefine -Uname
+$makemake -r test
+
+if diff expected res/file && diff expected-undefine res-undefine/file; then
+    echo success
+    rm -r res res-undefine
+    exit 0
+else
+    echo failure
+    rm -r res res-undefine
+    exit 1
+fi
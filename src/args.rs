@@ -1,11 +1,13 @@
 use std::{
     borrow::Cow,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     io::{stdout, IsTerminal},
 };
 
 use pareg::{has_any_key, key_mval_arg, FromArg, Pareg, Result};
 
+use crate::value::Value;
+
 /// Yes/No/Auto
 #[derive(FromArg, Clone, Copy, PartialEq, Eq)]
 pub enum Yna {
@@ -36,8 +38,13 @@ pub struct Args {
     pub template: String,
     pub directory: Cow<'static, str>,
     pub action: Action,
-    pub vars: HashMap<Cow<'static, str>, Cow<'static, str>>,
+    pub vars: HashMap<Cow<'static, str>, Value<'static>>,
+    pub undefine: HashSet<Cow<'static, str>>,
+    pub vars_files: Vec<String>,
     pub prompt_answer: Yna,
+    pub explain_vars: bool,
+    pub explain_files: bool,
+    pub verbose: bool,
 }
 
 impl Args {
@@ -46,6 +53,11 @@ impl Args {
         let mut template = None;
         let mut directory: Cow<'static, str> = ".".into();
         let mut vars = HashMap::new();
+        let mut undefine = HashSet::new();
+        let mut vars_files = vec![];
+        let mut explain_vars = false;
+        let mut explain_files = false;
+        let mut verbose = false;
         let mut prompt_answer = Yna::Auto;
         let mut action = None;
 
@@ -110,17 +122,36 @@ impl Args {
                 "-py" => prompt_answer = Yna::Yes,
                 "-pn" => prompt_answer = Yna::No,
                 "-pa" => prompt_answer = Yna::Auto,
+                "--explain-vars" => explain_vars = true,
+                "--explain" => explain_files = true,
+                "-v" | "--verbose" => verbose = true,
+                "--vars-file" => vars_files.push(args.next_arg()?),
+                "--seed" => {
+                    let seed: String = args.next_arg()?;
+                    undefine.remove("_SEED");
+                    vars.insert("_SEED".into(), seed.into());
+                }
                 v if has_any_key!(v, '=', "--color", "--colour") => {
                     use_color = args.cur_val_or_next('=')?;
                 }
                 arg if arg.starts_with("-D") => {
                     let arg = &arg[2..];
                     let (k, v) = key_mval_arg::<String, String>(arg, '=')?;
+                    undefine.remove(k.as_str());
                     vars.insert(
                         k.into(),
-                        v.map(|v| v.into()).unwrap_or("".into()),
+                        v.map(|v| v.into()).unwrap_or_default(),
                     );
                 }
+                arg if arg.starts_with("-U") => {
+                    let k: String = if arg.len() == 2 {
+                        args.next_arg()?
+                    } else {
+                        arg[2..].to_owned()
+                    };
+                    vars.remove(k.as_str());
+                    undefine.insert(k.into());
+                }
                 arg if arg.starts_with('-') => {
                     return args
                         .err_unknown_argument()
@@ -161,7 +192,12 @@ impl Args {
                 directory,
                 action,
                 vars,
+                undefine,
+                vars_files,
                 prompt_answer,
+                explain_vars,
+                explain_files,
+                verbose,
             })
         }
     } // fn parse
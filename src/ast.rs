@@ -1,14 +1,25 @@
 use std::{
-    borrow::Cow, collections::HashMap, fmt::Write, fs::File, io::BufReader,
-    mem,
+    borrow::Cow,
+    collections::HashMap,
+    fmt::{Display, Write},
+    fs, mem,
+    path::Path,
 };
 
-use utf8_chars::BufReadCharsExt;
-
 use crate::{
+    encoding::{base64, base64_decode, hex, latin1_decode, md5, sha256},
     err::{Error, Result},
-    maker::{expand, ExpandContext},
-    writer::FakeWriter,
+    escape::{json_escape, toml_string, url_encode, xml_escape, yaml_string},
+    maker::{expand_data, Encoding, ExpandContext},
+    paths::{
+        basename, dirname, extension, join_path, rel_path, with_extension,
+    },
+    random::Random,
+    scope::Scope,
+    text::{dedent, first_line, prefix_lines, wrap},
+    value::Value,
+    version::Version,
+    writer::{ByteWrite, FakeWriter},
 };
 
 pub enum Expr {
@@ -20,9 +31,11 @@ pub enum Expr {
     Condition(Condition),
     NullCheck(NullCheck),
     Call(Call),
+    Switch(Switch),
+    Arith(Arith),
+    Compare(Compare),
 }
 
-#[derive(Hash, PartialEq, Eq)]
 pub struct Variable(String);
 pub struct Literal(String);
 pub struct Concat(Vec<Expr>);
@@ -39,17 +52,56 @@ pub struct NullCheck {
     other: Box<Expr>,
 }
 
+pub struct Switch {
+    value: Box<Expr>,
+    cases: Vec<(Expr, Expr)>,
+    default: Option<Box<Expr>>,
+}
+
+#[derive(Clone, Copy)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+pub struct Arith {
+    op: ArithOp,
+    l: Box<Expr>,
+    r: Box<Expr>,
+}
+
+#[derive(Clone, Copy)]
+pub enum CmpOp {
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+}
+
+pub struct Compare {
+    op: CmpOp,
+    l: Box<Expr>,
+    r: Box<Expr>,
+}
+
+pub enum Arg {
+    Value(Expr),
+    Define(Variable, Expr),
+    Undefine(Variable),
+}
+
 pub struct Call {
     typ: Variable,
-    file: Box<Expr>,
-    define: HashMap<Variable, Expr>,
-    undefine: Vec<Variable>,
+    args: Vec<Arg>,
 }
 
 impl Expr {
-    pub fn eval<W>(self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    pub fn eval<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
     where
-        W: Write,
+        W: ByteWrite,
     {
         match self {
             Self::None => Ok(false),
@@ -60,6 +112,60 @@ impl Expr {
             Self::Condition(c) => c.eval(res, ctx),
             Self::NullCheck(n) => n.eval(res, ctx),
             Self::Call(c) => c.eval(res, ctx),
+            Self::Switch(s) => s.eval(res, ctx),
+            Self::Arith(a) => a.eval(res, ctx),
+            Self::Compare(c) => c.eval(res, ctx),
+        }
+    }
+
+    /// Evaluates the expression as integer. Returns `None` if the result is
+    /// undefined and fails if it is not integer.
+    pub fn number(&self, ctx: ExpandContext) -> Result<Option<i64>> {
+        self.string(ctx)?.map(|s| parse_number(&s)).transpose()
+    }
+
+    /// Evaluates the expression to string. Returns `None` if the result is
+    /// undefined.
+    pub fn string(&self, ctx: ExpandContext) -> Result<Option<String>> {
+        let mut res = String::new();
+        if self.eval(&mut res, ctx)? {
+            Ok(Some(res))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Evaluates the expression to bytes. `#include` reads the file as it
+    /// is, so it doesn't have to be valid utf-8.
+    pub fn bytes(&self, ctx: ExpandContext) -> Result<Option<Vec<u8>>> {
+        match self {
+            Self::Call(c) if c.typ.0 == "include" => c.include_bytes(ctx),
+            _ => Ok(self.string(ctx)?.map(String::into_bytes)),
+        }
+    }
+
+    /// Evaluates the expression to value. Variables keep their structure,
+    /// other expressions evaluate to string. Returns `None` if the result is
+    /// undefined.
+    pub fn value<'a>(
+        &self,
+        ctx: ExpandContext<'a>,
+    ) -> Result<Option<Cow<'a, Value<'a>>>> {
+        match self {
+            Self::Variable(v) => return v.value(ctx),
+            Self::Call(c) => {
+                if let Some(v) = c.value(ctx)? {
+                    return Ok(v.map(Cow::Owned));
+                }
+            }
+            _ => {}
+        }
+
+        let mut res = String::new();
+        if self.eval(&mut res, ctx)? {
+            Ok(Some(Cow::Owned(res.into())))
+        } else {
+            Ok(None)
         }
     }
 
@@ -117,22 +223,71 @@ impl From<Call> for Expr {
     }
 }
 
+impl From<Switch> for Expr {
+    fn from(value: Switch) -> Self {
+        Self::Switch(value)
+    }
+}
+
+impl From<Arith> for Expr {
+    fn from(value: Arith) -> Self {
+        Self::Arith(value)
+    }
+}
+
+impl From<Compare> for Expr {
+    fn from(value: Compare) -> Self {
+        Self::Compare(value)
+    }
+}
+
 impl Variable {
     pub fn new(name: String) -> Self {
         Self(name)
     }
 
-    pub fn eval<W>(self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    pub fn eval<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
     where
-        W: Write,
+        W: ByteWrite,
     {
-        if let Some(v) = ctx.vars.get(&Cow::Owned(self.0)) {
-            res.write_str(v)?;
+        if let Some(v) = self.value(ctx)? {
+            write!(res, "{v}")?;
             Ok(true)
         } else {
             Ok(false)
         }
     }
+
+    /// Gets the value of the variable. Members of lists and maps are
+    /// accessed with `.`.
+    pub fn value<'a>(
+        &self,
+        ctx: ExpandContext<'a>,
+    ) -> Result<Option<Cow<'a, Value<'a>>>> {
+        let (name, path) = match self.0.split_once('.') {
+            Some((n, p)) => (n, Some(p)),
+            None => (self.0.as_str(), None),
+        };
+
+        let value = ctx.get(name)?;
+        ctx.cache.read(name, value.as_deref());
+        let Some(value) = value else {
+            return Ok(None);
+        };
+
+        let Some(path) = path else {
+            return Ok(Some(value));
+        };
+
+        Ok(match value {
+            Cow::Borrowed(v) => v.path(path).map(Cow::Borrowed),
+            Cow::Owned(v) => v.path(path).map(|v| Cow::Owned(v.clone())),
+        })
+    }
 }
 
 impl Literal {
@@ -140,9 +295,9 @@ impl Literal {
         Self(value)
     }
 
-    pub fn eval<W>(self, res: &mut W) -> Result<bool>
+    pub fn eval<W>(&self, res: &mut W) -> Result<bool>
     where
-        W: Write,
+        W: ByteWrite,
     {
         res.write_str(&self.0)?;
         Ok(true)
@@ -154,12 +309,12 @@ impl Concat {
         Self(exprs)
     }
 
-    pub fn eval<W>(self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    pub fn eval<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
     where
-        W: Write,
+        W: ByteWrite,
     {
         self.0
-            .into_iter()
+            .iter()
             .map(|e| e.eval(res, ctx))
             .try_fold(false, |a, b| Ok(a | b?))
     }
@@ -170,9 +325,9 @@ impl Equals {
         Self(Box::new(l), Box::new(r))
     }
 
-    pub fn eval<W>(self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    pub fn eval<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
     where
-        W: Write,
+        W: ByteWrite,
     {
         let mut l = String::new();
         let mut r = String::new();
@@ -197,9 +352,9 @@ impl Condition {
         }
     }
 
-    pub fn eval<W>(self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    pub fn eval<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
     where
-        W: Write,
+        W: ByteWrite,
     {
         let mut w = FakeWriter;
         if self.cond.eval(&mut w, ctx)? {
@@ -218,9 +373,9 @@ impl NullCheck {
         }
     }
 
-    pub fn eval<W>(self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    pub fn eval<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
     where
-        W: Write,
+        W: ByteWrite,
     {
         let mut w = String::new();
         if self.cond.eval(&mut w, ctx)? {
@@ -232,113 +387,745 @@ impl NullCheck {
     }
 }
 
-impl Call {
+impl Switch {
     pub fn new(
-        typ: Variable,
-        file: Expr,
-        define: HashMap<Variable, Expr>,
-        undefine: Vec<Variable>,
+        value: Expr,
+        cases: Vec<(Expr, Expr)>,
+        default: Option<Expr>,
     ) -> Self {
         Self {
-            typ,
-            file: Box::new(file),
-            define,
-            undefine,
+            value: Box::new(value),
+            cases,
+            default: default.map(Box::new),
+        }
+    }
+
+    pub fn eval<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let mut value = String::new();
+        let defined = self.value.eval(&mut value, ctx)?;
+
+        for (case, branch) in &self.cases {
+            let mut c = String::new();
+            if case.eval(&mut c, ctx)? == defined && c == value {
+                return branch.eval(res, ctx);
+            }
+        }
+
+        match &self.default {
+            Some(d) => d.eval(res, ctx),
+            None => Ok(false),
+        }
+    }
+}
+
+impl Arith {
+    pub fn new(op: ArithOp, l: Expr, r: Expr) -> Self {
+        Self {
+            op,
+            l: Box::new(l),
+            r: Box::new(r),
         }
     }
 
-    pub fn eval<W>(self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    pub fn eval<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
     where
-        W: Write,
+        W: ByteWrite,
+    {
+        let (Some(l), Some(r)) = (self.l.number(ctx)?, self.r.number(ctx)?)
+        else {
+            return Ok(false);
+        };
+
+        let v = match self.op {
+            ArithOp::Add => l.checked_add(r),
+            ArithOp::Sub => l.checked_sub(r),
+            ArithOp::Mul => l.checked_mul(r),
+            ArithOp::Div | ArithOp::Rem if r == 0 => {
+                return Err(Error::Msg("Division by zero.".into()));
+            }
+            ArithOp::Div => l.checked_div(r),
+            ArithOp::Rem => l.checked_rem(r),
+        };
+
+        let Some(v) = v else {
+            return Err(Error::Msg(
+                format!("Arithmetic overflow in `{l} {} {r}`.", self.op)
+                    .into(),
+            ));
+        };
+
+        write!(res, "{v}")?;
+        Ok(true)
+    }
+}
+
+impl Display for ArithOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Add => f.write_char('+'),
+            Self::Sub => f.write_char('-'),
+            Self::Mul => f.write_char('*'),
+            Self::Div => f.write_char('/'),
+            Self::Rem => f.write_char('%'),
+        }
+    }
+}
+
+impl Compare {
+    pub fn new(op: CmpOp, l: Expr, r: Expr) -> Self {
+        Self {
+            op,
+            l: Box::new(l),
+            r: Box::new(r),
+        }
+    }
+
+    pub fn eval<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let mut l = String::new();
+        let mut r = String::new();
+        if !self.l.eval(&mut l, ctx)? || !self.r.eval(&mut r, ctx)? {
+            return Ok(false);
+        }
+
+        let (ln, rn) = (parse_number(&l)?, parse_number(&r)?);
+        let success = match self.op {
+            CmpOp::Less => ln < rn,
+            CmpOp::LessEq => ln <= rn,
+            CmpOp::Greater => ln > rn,
+            CmpOp::GreaterEq => ln >= rn,
+        };
+
+        if success {
+            res.write_str(&l)?;
+        }
+        Ok(success)
+    }
+}
+
+impl Call {
+    pub fn new(typ: Variable, args: Vec<Arg>) -> Self {
+        Self { typ, args }
+    }
+
+    pub fn eval<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    where
+        W: ByteWrite,
     {
         match self.typ.0.as_str() {
             "exists" => self.exists(ctx),
             "include" => self.include(res, ctx),
             "make" => self.make(res, ctx),
+            "len" => self.len(res, ctx),
+            "each" => self.each(res, ctx),
+            "pad" => self.pad(res, ctx),
+            "semverCmp" => self.semver_cmp(res, ctx),
+            "versionAtLeast" => self.version_at_least(res, ctx),
+            "semverBump" => self.semver_bump(res, ctx),
+            "jsonEscape" => self.map_str(res, ctx, json_escape),
+            "tomlString" => self.map_str(res, ctx, toml_string),
+            "yamlString" => self.map_str(res, ctx, yaml_string),
+            "xmlEscape" => self.map_str(res, ctx, xml_escape),
+            "shellQuote" => {
+                self.map_str(res, ctx, |s| shell_words::quote(s).into_owned())
+            }
+            "urlEncode" => self.map_str(res, ctx, url_encode),
+            "basename" => self.map_str(res, ctx, basename),
+            "dirname" => self.map_str(res, ctx, dirname),
+            "extension" => self.extension(res, ctx),
+            "withExtension" => self.with_extension(res, ctx),
+            "joinPath" => self.join_path(res, ctx),
+            "relPath" => self.rel_path(res, ctx),
+            "indent" => self.indent(res, ctx),
+            "prefixLines" => self.prefix_lines(res, ctx),
+            "wrap" => self.wrap(res, ctx),
+            "dedent" => self.map_str(res, ctx, dedent),
+            "firstLine" => self.map_str(res, ctx, |s| first_line(s).into()),
+            "uuid" => {
+                let [] = self.args()?;
+                ctx.cache.impure();
+                res.write_str(&ctx.random.uuid())?;
+                Ok(true)
+            }
+            "randomHex" => self.random(res, ctx, Random::hex),
+            "randomAlnum" => self.random(res, ctx, Random::alnum),
+            "sha256" => self.map_bytes(res, ctx, sha256),
+            "md5" => self.map_bytes(res, ctx, md5),
+            "base64" => self.map_bytes(res, ctx, base64),
+            "base64Decode" => self.base64_decode(res, ctx),
+            "hex" => self.map_bytes(res, ctx, hex),
+            "lines" => match self.value(ctx)? {
+                Some(Some(v)) => {
+                    write!(res, "{v}")?;
+                    Ok(true)
+                }
+                _ => Ok(false),
+            },
             a => Err(Error::Msg(format!("Unknown function '{a}'").into())),
         }
     }
 
-    pub fn exists(self, ctx: ExpandContext) -> Result<bool> {
-        if !self.define.is_empty() || !self.undefine.is_empty() {
-            return Err(Error::Msg(
-                "Too many arguments to function '#include'".into(),
-            ));
+    /// Evaluates the functions that return structured value. Returns `None`
+    /// if the function returns only string.
+    pub fn value(
+        &self,
+        ctx: ExpandContext,
+    ) -> Result<Option<Option<Value<'static>>>> {
+        match self.typ.0.as_str() {
+            "lines" => self.lines(ctx).map(Some),
+            _ => Ok(None),
         }
+    }
 
-        let mut file = String::new();
-        self.file.eval(&mut file, ctx)?;
-        let file = ctx.template_dir.join(file);
+    pub fn exists(&self, ctx: ExpandContext) -> Result<bool> {
+        let [file] = self.args()?;
+
+        let mut path = String::new();
+        file.eval(&mut path, ctx)?;
+        let file = ctx.template_dir.join(path);
         Ok(file.exists())
     }
 
-    pub fn include<W>(self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    pub fn include<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let path = self.include_path(ctx)?;
+        let file = ctx.template_dir.join(&path);
+        if !file.exists() {
+            return Ok(false);
+        }
+
+        let data = String::from_utf8(fs::read(file)?).map_err(|_| {
+            Error::Msg(
+                format!(
+                    "File '{path}' included with '#include' is not valid \
+                    utf-8, only '#sha256', '#md5', '#base64' and '#hex' may \
+                    take it directly."
+                )
+                .into(),
+            )
+        })?;
+        res.write_str(&data)?;
+
+        Ok(true)
+    }
+
+    /// Reads the file given to `#include` as it is. Returns `None` if the
+    /// file doesn't exist.
+    fn include_bytes(&self, ctx: ExpandContext) -> Result<Option<Vec<u8>>> {
+        let path = self.include_path(ctx)?;
+        let file = ctx.template_dir.join(path);
+        if !file.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(fs::read(file)?))
+    }
+
+    fn include_path(&self, ctx: ExpandContext) -> Result<String> {
+        let [file] = self.args()?;
+
+        let mut path = String::new();
+        file.eval(&mut path, ctx)?;
+        Ok(path)
+    }
+
+    pub fn make<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
     where
-        W: Write,
+        W: ByteWrite,
     {
-        if !self.define.is_empty() || !self.undefine.is_empty() {
+        let Some((Arg::Value(file), args)) = self.args.split_first() else {
             return Err(Error::Msg(
-                "Too many arguments to function '#include'".into(),
+                "Function '#make' expects file as the first argument.".into(),
             ));
-        }
+        };
 
-        let mut file = String::new();
-        self.file.eval(&mut file, ctx)?;
-        let file = ctx.template_dir.join(file);
+        let mut path = String::new();
+        file.eval(&mut path, ctx)?;
+        let file = ctx.template_dir.join(&path);
         if !file.exists() {
             return Ok(false);
         }
 
-        let mut file = BufReader::new(File::open(file)?);
-        for c in file.chars() {
-            res.write_char(c?)?;
+        ctx.budget.enter(&path)?;
+        let r = Self::make_file(&file, args, res, ctx);
+        ctx.budget.leave();
+        r?;
+
+        Ok(true)
+    }
+
+    /// Expands the file for `#make` with the variables modified by `args`.
+    fn make_file<W>(
+        file: &Path,
+        args: &[Arg],
+        res: &mut W,
+        ctx: ExpandContext,
+    ) -> Result<()>
+    where
+        W: ByteWrite,
+    {
+        let mut vars = HashMap::new();
+        for a in args {
+            match a {
+                Arg::Undefine(k) => {
+                    vars.insert(k.0.as_str().into(), None);
+                }
+                Arg::Define(k, v) => {
+                    let value = v.value(ctx)?.unwrap_or_default().into_owned();
+                    vars.insert(k.0.as_str().into(), Some(value));
+                }
+                // Variable without value is defined as empty
+                Arg::Value(Expr::Variable(k)) => {
+                    vars.insert(k.0.as_str().into(), Some(Value::default()));
+                }
+                Arg::Value(_) => {
+                    return Err(Error::Msg(
+                        "Function '#make' expects only variable definitions \
+                        after the file."
+                            .into(),
+                    ));
+                }
+            }
+        }
+
+        let parent = ctx.vars;
+        let ctx = if vars.is_empty() {
+            ctx
+        } else {
+            ExpandContext {
+                vars: Scope::Nested {
+                    parent: &parent,
+                    vars: &vars,
+                },
+                ..ctx
+            }
+        };
+
+        let local: Vec<_> = vars.keys().map(|k| k.to_string()).collect();
+        let out = if let Some(out) = ctx.cache.get(file, &local, ctx)? {
+            out
+        } else {
+            ctx.cache.enter(local);
+            let r = ctx.budget.buffered(|| {
+                fs::read(file)
+                    .map_err(Error::from)
+                    .and_then(|f| expand_data(ctx, &f))
+            });
+            let out = r.as_deref().ok();
+            ctx.cache.leave(file, ctx.encoding, out);
+            r?
+        };
+
+        ctx.budget.check_output(out.len())?;
+        // the bytes are passed through in the encoding of the file
+        match ctx.encoding {
+            Encoding::Utf8 => res.write_bytes(&out)?,
+            Encoding::Latin1 => res.write_str(&latin1_decode(&out))?,
+        }
+        Ok(())
+    }
+
+    pub fn len<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let [value] = self.args()?;
+
+        if let Some(v) = value.value(ctx)? {
+            write!(res, "{}", v.len())?;
+            Ok(true)
+        } else {
+            Ok(false)
         }
+    }
 
+    pub fn each<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let [Arg::Value(list), Arg::Define(item, body)] = &self.args[..]
+        else {
+            return Err(Error::Msg(
+                "Function '#each' expects list and exactly one variable \
+                definition."
+                    .into(),
+            ));
+        };
+
+        let Some(list) = list.value(ctx)? else {
+            return Ok(false);
+        };
+
+        // the items don't come from the caller of the `#make`
+        let bound = [item.0.as_str(), "_INDEX", "_KEY"];
+        ctx.cache.bind(&bound);
+        let mut vars = HashMap::new();
+        let mut eval = || {
+            let mut defined = false;
+            for (i, (key, value)) in list.items().into_iter().enumerate() {
+                vars.insert(item.0.as_str().into(), Some(value.clone()));
+                vars.insert("_INDEX".into(), Some(i.to_string().into()));
+                vars.insert("_KEY".into(), Some(key.into_owned().into()));
+
+                let vars = Scope::Nested {
+                    parent: &ctx.vars,
+                    vars: &vars,
+                };
+                defined |= body.eval(res, ExpandContext { vars, ..ctx })?;
+            }
+            Ok(defined)
+        };
+        let r = eval();
+        ctx.cache.unbind(bound.len());
+        r
+    }
+
+    pub fn pad<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let [n, width] = self.args()?;
+
+        let (Some(n), Some(width)) = (n.number(ctx)?, width.number(ctx)?)
+        else {
+            return Ok(false);
+        };
+
+        let width = usize::try_from(width).map_err(|_| {
+            Error::Msg(format!("Invalid width {width} for '#pad'.").into())
+        })?;
+        // the width in format is limited to u16
+        let digits = n.unsigned_abs().to_string();
+        let sign = if n < 0 { "-" } else { "" };
+        let zeros = width.saturating_sub(sign.len() + digits.len());
+        ctx.budget.check_output(zeros)?;
+        write!(res, "{sign}{}{digits}", "0".repeat(zeros))?;
         Ok(true)
     }
 
-    pub fn make<W>(self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    pub fn semver_cmp<W>(
+        &self,
+        res: &mut W,
+        ctx: ExpandContext,
+    ) -> Result<bool>
     where
-        W: Write,
+        W: ByteWrite,
     {
-        let mut file = String::new();
-        self.file.eval(&mut file, ctx)?;
-        let file = ctx.template_dir.join(file);
-        if !file.exists() {
+        let [a, b] = self.args()?;
+        let (Some(a), Some(b)) = (a.string(ctx)?, b.string(ctx)?) else {
             return Ok(false);
-        }
+        };
 
-        let mut file = BufReader::new(File::open(file)?);
-        if self.define.is_empty() && self.undefine.is_empty() {
-            expand(ctx, &mut file.chars().map(|a| Ok(a?)), res)?;
-            return Ok(true);
+        let ord = a.parse::<Version>()?.cmp(&b.parse()?);
+        write!(res, "{}", ord as i8)?;
+        Ok(true)
+    }
+
+    pub fn version_at_least<W>(
+        &self,
+        res: &mut W,
+        ctx: ExpandContext,
+    ) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let [v, min] = self.args()?;
+        let (Some(v), Some(min)) = (v.string(ctx)?, min.string(ctx)?) else {
+            return Ok(false);
+        };
+
+        if v.parse::<Version>()? >= min.parse()? {
+            res.write_str(&v)?;
+            Ok(true)
+        } else {
+            Ok(false)
         }
+    }
 
-        let mut vars: HashMap<Cow<_>, Cow<_>> = ctx
-            .vars
-            .iter()
-            .map(|(k, v)| (k.as_ref().into(), v.as_ref().into()))
-            .collect();
+    pub fn semver_bump<W>(
+        &self,
+        res: &mut W,
+        ctx: ExpandContext,
+    ) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let [v, part] = self.args()?;
+        let (Some(v), Some(part)) = (v.string(ctx)?, part.string(ctx)?) else {
+            return Ok(false);
+        };
+
+        let mut v: Version = v.parse()?;
+        v.bump(&part)?;
+        write!(res, "{v}")?;
+        Ok(true)
+    }
 
-        for k in self.undefine {
-            vars.remove(k.0.as_str());
+    pub fn extension<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let [path] = self.args()?;
+        let Some(path) = path.string(ctx)? else {
+            return Ok(false);
+        };
+
+        if let Some(ext) = extension(&path) {
+            res.write_str(ext)?;
+            Ok(true)
+        } else {
+            Ok(false)
         }
+    }
 
-        for (k, v) in self.define {
-            let mut value = String::new();
-            v.eval(&mut value, ctx)?;
-            vars.insert(k.0.into(), value.into());
+    pub fn with_extension<W>(
+        &self,
+        res: &mut W,
+        ctx: ExpandContext,
+    ) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let [path, ext] = self.args()?;
+        let (Some(path), Some(ext)) = (path.string(ctx)?, ext.string(ctx)?)
+        else {
+            return Ok(false);
+        };
+
+        res.write_str(&with_extension(&path, &ext))?;
+        Ok(true)
+    }
+
+    pub fn join_path<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let mut parts = vec![];
+        for a in self.values()? {
+            let Some(v) = a.value(ctx)? else {
+                return Ok(false);
+            };
+            match v.as_ref() {
+                Value::Str(s) => parts.push(s.to_string()),
+                v => {
+                    parts.extend(v.items().iter().map(|(_, v)| v.to_string()))
+                }
+            }
         }
 
-        expand(
-            ExpandContext {
-                vars: &vars,
-                template_dir: ctx.template_dir,
-            },
-            &mut file.chars().map(|a| Ok(a?)),
-            res,
-        )?;
+        res.write_str(&join_path(parts.iter().map(|p| p.as_str())))?;
+        Ok(true)
+    }
+
+    pub fn rel_path<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let [from, to] = self.args()?;
+        let (Some(from), Some(to)) = (from.string(ctx)?, to.string(ctx)?)
+        else {
+            return Ok(false);
+        };
+
+        res.write_str(&rel_path(&from, &to)?)?;
+        Ok(true)
+    }
+
+    pub fn indent<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let [text, n] = self.args()?;
+        let (Some(text), Some(n)) = (text.string(ctx)?, n.number(ctx)?) else {
+            return Ok(false);
+        };
+
+        let n = usize::try_from(n).map_err(|_| {
+            Error::Msg(
+                format!("Invalid indentation {n} for '#indent'.").into(),
+            )
+        })?;
+        let lines = text.lines().count().max(1);
+        ctx.budget.check_output(n.saturating_mul(lines))?;
+        res.write_str(&prefix_lines(&text, &" ".repeat(n)))?;
+        Ok(true)
+    }
+
+    pub fn prefix_lines<W>(
+        &self,
+        res: &mut W,
+        ctx: ExpandContext,
+    ) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let [text, prefix] = self.args()?;
+        let (Some(text), Some(prefix)) =
+            (text.string(ctx)?, prefix.string(ctx)?)
+        else {
+            return Ok(false);
+        };
+
+        res.write_str(&prefix_lines(&text, &prefix))?;
+        Ok(true)
+    }
+
+    pub fn wrap<W>(&self, res: &mut W, ctx: ExpandContext) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let [text, width] = self.args()?;
+        let (Some(text), Some(width)) =
+            (text.string(ctx)?, width.number(ctx)?)
+        else {
+            return Ok(false);
+        };
+
+        let width = usize::try_from(width).map_err(|_| {
+            Error::Msg(format!("Invalid width {width} for '#wrap'.").into())
+        })?;
+        res.write_str(&wrap(&text, width))?;
+        Ok(true)
+    }
+
+    pub fn base64_decode<W>(
+        &self,
+        res: &mut W,
+        ctx: ExpandContext,
+    ) -> Result<bool>
+    where
+        W: ByteWrite,
+    {
+        let [data] = self.args()?;
+        let Some(data) = data.string(ctx)? else {
+            return Ok(false);
+        };
+
+        res.write_str(&base64_decode(&data)?)?;
+        Ok(true)
+    }
+
+    /// Evaluates function that generates `n` random characters with `f`.
+    fn random<W, F>(
+        &self,
+        res: &mut W,
+        ctx: ExpandContext,
+        f: F,
+    ) -> Result<bool>
+    where
+        W: ByteWrite,
+        F: FnOnce(&Random, usize) -> String,
+    {
+        let [n] = self.args()?;
+        let Some(n) = n.number(ctx)? else {
+            return Ok(false);
+        };
+        ctx.cache.impure();
+
+        let n = usize::try_from(n).map_err(|_| {
+            Error::Msg(
+                format!("Invalid length {n} for '#{}'.", self.typ.0).into(),
+            )
+        })?;
+        ctx.budget.check_output(n)?;
+        res.write_str(&f(ctx.random, n))?;
+        Ok(true)
+    }
+
+    pub fn lines(&self, ctx: ExpandContext) -> Result<Option<Value<'static>>> {
+        let [text] = self.args()?;
+        let Some(text) = text.string(ctx)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(Value::List(
+            text.lines().map(|l| l.to_owned().into()).collect(),
+        )))
+    }
+
+    /// Same as `map_str`, but the function gets bytes, so that `#include`
+    /// given directly as the argument may read file that is not valid utf-8.
+    fn map_bytes<W, F>(
+        &self,
+        res: &mut W,
+        ctx: ExpandContext,
+        f: F,
+    ) -> Result<bool>
+    where
+        W: ByteWrite,
+        F: FnOnce(&[u8]) -> String,
+    {
+        let [s] = self.args()?;
+        let Some(s) = s.bytes(ctx)? else {
+            return Ok(false);
+        };
 
+        res.write_str(&f(&s))?;
         Ok(true)
     }
+
+    /// Evaluates function that takes single string and transforms it with
+    /// `f`.
+    fn map_str<W, F>(
+        &self,
+        res: &mut W,
+        ctx: ExpandContext,
+        f: F,
+    ) -> Result<bool>
+    where
+        W: ByteWrite,
+        F: FnOnce(&str) -> String,
+    {
+        let [s] = self.args()?;
+        let Some(s) = s.string(ctx)? else {
+            return Ok(false);
+        };
+
+        res.write_str(&f(&s))?;
+        Ok(true)
+    }
+
+    /// Gets the arguments of function that takes exactly `N` expressions.
+    fn args<const N: usize>(&self) -> Result<[&Expr; N]> {
+        self.values()?.try_into().map_err(|_| {
+            Error::Msg(
+                format!(
+                    "Function '#{}' expects {N} argument{}.",
+                    self.typ.0,
+                    if N == 1 { "" } else { "s" }
+                )
+                .into(),
+            )
+        })
+    }
+
+    /// Gets the arguments of function that takes only expressions.
+    fn values(&self) -> Result<Vec<&Expr>> {
+        self.args
+            .iter()
+            .map(|a| match a {
+                Arg::Value(e) => Ok(e),
+                _ => Err(Error::Msg(
+                    format!(
+                        "Function '#{}' doesn't accept variable definitions.",
+                        self.typ.0
+                    )
+                    .into(),
+                )),
+            })
+            .collect()
+    }
+}
+
+/// Parses integer. Fails if `s` is not integer.
+fn parse_number(s: &str) -> Result<i64> {
+    s.parse()
+        .map_err(|_| Error::Msg(format!("'{s}' is not a number.").into()))
 }
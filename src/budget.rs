@@ -0,0 +1,158 @@
+use std::{
+    cell::{Cell, RefCell},
+    fmt::Write,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::err::{Error, Result};
+
+/// Limits for the evaluation of the template.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    /// Maximum nesting of `#make`.
+    #[serde(rename = "maxDepth")]
+    pub max_depth: usize,
+    /// Maximum number of evaluated expressions.
+    #[serde(rename = "maxSteps")]
+    pub max_steps: u64,
+    /// Maximum number of bytes written by the template.
+    #[serde(rename = "maxOutput")]
+    pub max_output: u64,
+}
+
+/// Tracks the resources used by the evaluation of the template, so that
+/// infinite recursion fails instead of overflowing the stack or running
+/// forever.
+#[derive(Default)]
+pub struct Budget {
+    limits: Limits,
+    steps: Cell<u64>,
+    output: Cell<u64>,
+    /// Files in the current chain of `#make` calls.
+    chain: RefCell<Vec<String>>,
+}
+
+impl Budget {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    /// Counts single evaluation step. Fails if the number of steps exceeds
+    /// the limit.
+    pub fn step(&self) -> Result<()> {
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+        if steps > self.limits.max_steps {
+            return Err(self.error(format!(
+                "Exceeded the maximum number of evaluation steps ({}).",
+                self.limits.max_steps
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks that `len` more bytes may be written without exceeding the
+    /// output limit. This is used before the output is allocated.
+    pub fn check_output(&self, len: usize) -> Result<()> {
+        let total = self.output.get().saturating_add(len as u64);
+        if total > self.limits.max_output {
+            Err(self.output_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Counts bytes written to the output. Fails if the total output
+    /// exceeds the limit.
+    pub fn add_output(&self, len: usize) -> Result<()> {
+        self.output.set(self.output.get() + len as u64);
+        self.check_total_output()
+    }
+
+    /// Fails if the total output exceeds the limit.
+    pub fn check_total_output(&self) -> Result<()> {
+        if self.output.get() > self.limits.max_output {
+            Err(self.output_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Runs `f` that writes to buffer that is then written to the output.
+    /// The output of `f` is counted only while it runs, because it is
+    /// counted again when the buffer is written.
+    pub fn buffered<R>(&self, f: impl FnOnce() -> R) -> R {
+        let output = self.output.get();
+        let res = f();
+        self.output.set(output);
+        res
+    }
+
+    /// Enters `#make` of the given file.
+    pub fn enter(&self, file: &str) -> Result<()> {
+        self.chain.borrow_mut().push(file.to_owned());
+        if self.depth() > self.limits.max_depth {
+            let err = self.error(format!(
+                "Exceeded the maximum depth of '#make' ({}).",
+                self.limits.max_depth
+            ));
+            self.leave();
+            return Err(err);
+        }
+        self.step()
+    }
+
+    /// Leaves the last entered `#make`.
+    pub fn leave(&self) {
+        self.chain.borrow_mut().pop();
+    }
+
+    /// Gets the current nesting of `#make`.
+    pub fn depth(&self) -> usize {
+        self.chain.borrow().len()
+    }
+
+    fn output_error(&self) -> Error {
+        self.error(format!(
+            "Exceeded the maximum output size ({} bytes).",
+            self.limits.max_output
+        ))
+    }
+
+    /// Creates error with the message and the chain of `#make` calls.
+    fn error(&self, mut msg: String) -> Error {
+        let chain = self.chain.borrow();
+        if !chain.is_empty() {
+            msg.push_str("\n'#make' call chain:");
+        }
+
+        // repeated calls of the same file are shown only once
+        let mut i = 0;
+        while i < chain.len() {
+            let cnt =
+                chain[i..].iter().take_while(|f| **f == chain[i]).count();
+            _ = write!(msg, "\n  {}", chain[i]);
+            if cnt > 1 {
+                _ = write!(msg, " ({cnt}x)");
+            }
+            i += cnt;
+        }
+
+        Error::Limit(msg)
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: 256,
+            max_steps: 10_000_000,
+            max_output: 256 * 1024 * 1024,
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    err::Result,
+    maker::{Encoding, ExpandContext},
+    value::Value,
+};
+
+/// Cache of the outputs of `#make`. The output of `#make` depends only on
+/// the file and on the values of the variables that it reads, unless it uses
+/// random functions.
+#[derive(Default)]
+pub struct MakeCache {
+    entries: RefCell<HashMap<(PathBuf, Encoding), Vec<Entry>>>,
+    /// `#make` calls that are currently evaluated.
+    frames: RefCell<Vec<Frame>>,
+    hits: Cell<usize>,
+    misses: Cell<usize>,
+}
+
+struct Entry {
+    reads: Vec<(String, Option<Value<'static>>)>,
+    output: Vec<u8>,
+}
+
+struct Frame {
+    /// Values of the variables read by the `#make`.
+    reads: HashMap<String, Option<Value<'static>>>,
+    /// Variables defined or undefined in the arguments of the `#make`.
+    local: Vec<String>,
+    /// Variables bound inside the `#make` (e.g. by `#each`). Reading them
+    /// doesn't depend on the caller.
+    bound: Vec<String>,
+    pure: bool,
+}
+
+impl MakeCache {
+    /// Gets the cached output of `#make` of the file. `local` are the
+    /// variables set in the arguments of the `#make` and `ctx` already
+    /// contains them.
+    pub fn get(
+        &self,
+        file: &Path,
+        local: &[String],
+        ctx: ExpandContext,
+    ) -> Result<Option<Vec<u8>>> {
+        let key = (file.to_owned(), ctx.encoding);
+        // The entries are taken out, because checking the variables may
+        // resolve lazy variables that also use `#make`.
+        let entries = self.entries.borrow_mut().remove(&key);
+        let entries = entries.unwrap_or_default();
+        let found = Self::find(&entries, ctx);
+
+        let res = found.map(|i| {
+            i.map(|i| {
+                let e = &entries[i];
+                if let Some(f) = self.frames.borrow_mut().last_mut() {
+                    for (k, v) in &e.reads {
+                        if !local.contains(k) && !f.bound.contains(k) {
+                            f.reads
+                                .entry(k.clone())
+                                .or_insert_with(|| v.clone());
+                        }
+                    }
+                }
+                e.output.clone()
+            })
+        });
+
+        self.entries
+            .borrow_mut()
+            .entry(key)
+            .or_default()
+            .extend(entries);
+
+        match &res {
+            Ok(Some(_)) => self.hits.set(self.hits.get() + 1),
+            Ok(None) => self.misses.set(self.misses.get() + 1),
+            Err(_) => {}
+        }
+        res
+    }
+
+    /// Starts recording the variables read by `#make`.
+    pub fn enter(&self, local: Vec<String>) {
+        self.frames.borrow_mut().push(Frame {
+            reads: HashMap::new(),
+            local,
+            bound: vec![],
+            pure: true,
+        });
+    }
+
+    /// Stops recording the variables read by the last entered `#make`. Its
+    /// output is cached if it is given and the `#make` was pure.
+    pub fn leave(
+        &self,
+        file: &Path,
+        encoding: Encoding,
+        output: Option<&[u8]>,
+    ) {
+        let mut frames = self.frames.borrow_mut();
+        let Some(frame) = frames.pop() else {
+            return;
+        };
+
+        // the parent depends on everything that its child reads from it
+        if let Some(f) = frames.last_mut() {
+            f.pure &= frame.pure;
+            for (k, v) in &frame.reads {
+                if !frame.local.contains(k) && !f.bound.contains(k) {
+                    f.reads.entry(k.clone()).or_insert_with(|| v.clone());
+                }
+            }
+        }
+
+        if let (true, Some(output)) = (frame.pure, output) {
+            self.entries
+                .borrow_mut()
+                .entry((file.to_owned(), encoding))
+                .or_default()
+                .push(Entry {
+                    reads: frame.reads.into_iter().collect(),
+                    output: output.to_owned(),
+                });
+        }
+    }
+
+    /// Records that variable was read.
+    pub fn read(&self, name: &str, value: Option<&Value>) {
+        if let Some(f) = self.frames.borrow_mut().last_mut() {
+            if !f.reads.contains_key(name)
+                && !f.bound.iter().any(|b| b == name)
+            {
+                f.reads
+                    .insert(name.to_owned(), value.map(|v| v.to_static()));
+            }
+        }
+    }
+
+    /// Marks the variables as bound inside the current `#make` until
+    /// [`Self::unbind`] is called with the same number of variables.
+    pub fn bind(&self, names: &[&str]) {
+        if let Some(f) = self.frames.borrow_mut().last_mut() {
+            f.bound.extend(names.iter().map(|n| n.to_string()));
+        }
+    }
+
+    /// Removes the last `cnt` variables bound with [`Self::bind`].
+    pub fn unbind(&self, cnt: usize) {
+        if let Some(f) = self.frames.borrow_mut().last_mut() {
+            f.bound.truncate(f.bound.len().saturating_sub(cnt));
+        }
+    }
+
+    /// Marks all the currently evaluated `#make` calls as impure so that
+    /// they are not cached.
+    pub fn impure(&self) {
+        for f in self.frames.borrow_mut().iter_mut() {
+            f.pure = false;
+        }
+    }
+
+    /// Gets the number of cache hits and misses.
+    pub fn stats(&self) -> (usize, usize) {
+        (self.hits.get(), self.misses.get())
+    }
+
+    /// Finds entry that matches the current values of the variables.
+    fn find(entries: &[Entry], ctx: ExpandContext) -> Result<Option<usize>> {
+        'entries: for (i, e) in entries.iter().enumerate() {
+            for (k, v) in &e.reads {
+                if ctx.get(k)?.as_deref() != v.as_ref() {
+                    continue 'entries;
+                }
+            }
+            return Ok(Some(i));
+        }
+        Ok(None)
+    }
+}
@@ -1,12 +1,15 @@
 use std::{borrow::Cow, collections::HashMap, path::Path, process::Command};
 
-use crate::err::{Error, Result};
+use crate::{
+    err::{Error, Result},
+    value::Value,
+};
 
 pub fn run_command<P1, P2>(
     cmd: &str,
     cwd: P1,
     pwd: P2,
-    vars: &HashMap<Cow<str>, Cow<str>>,
+    vars: &HashMap<Cow<str>, Value>,
 ) -> Result<()>
 where
     P1: AsRef<Path>,
@@ -24,7 +27,7 @@ where
     let com = Command::new(program.as_ref())
         .args(args)
         .current_dir(pwd)
-        .envs(vars.iter().map(|(k, v)| (k.as_ref(), v.as_ref())))
+        .envs(vars.iter().map(|(k, v)| (k.as_ref(), v.to_string())))
         .output()?;
     if !com.status.success() {
         let s = String::from_utf8_lossy(&com.stderr).into_owned();
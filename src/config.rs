@@ -2,17 +2,17 @@ use std::{borrow::Cow, collections::HashMap, fs::File, path::Path};
 
 use serde::{Deserialize, Serialize};
 
-use crate::err::Result;
+use crate::{err::Result, value::Value};
 
 #[derive(Serialize, Deserialize)]
 pub struct Alias {
     pub template: String,
-    pub vars: HashMap<Cow<'static, str>, Cow<'static, str>>,
+    pub vars: HashMap<Cow<'static, str>, Value<'static>>,
 }
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct Config {
-    pub vars: HashMap<Cow<'static, str>, Cow<'static, str>>,
+    pub vars: HashMap<Cow<'static, str>, Value<'static>>,
     pub aliases: HashMap<String, Alias>,
 }
 
@@ -0,0 +1,60 @@
+use std::fmt::Write;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use crate::err::{Error, Result};
+
+/// Gets the sha256 hash of the data as lowercase hex.
+pub fn sha256(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+/// Gets the md5 hash of the data as lowercase hex.
+pub fn md5(data: &[u8]) -> String {
+    hex(&Md5::digest(data))
+}
+
+/// Encodes the data with standard base64 (with padding).
+pub fn base64(data: &[u8]) -> String {
+    STANDARD.encode(data)
+}
+
+/// Decodes standard base64. The decoded data must be valid utf-8.
+pub fn base64_decode(s: &str) -> Result<String> {
+    let data = STANDARD.decode(s.trim()).map_err(|e| {
+        Error::Msg(format!("Invalid base64 '{s}': {e}").into())
+    })?;
+    String::from_utf8(data).map_err(|_| {
+        Error::Msg(format!("Base64 '{s}' doesn't decode to utf-8.").into())
+    })
+}
+
+/// Encodes the data as lowercase hex.
+pub fn hex(data: &[u8]) -> String {
+    let mut res = String::with_capacity(data.len() * 2);
+    for b in data {
+        _ = write!(res, "{b:02x}");
+    }
+    res
+}
+
+/// Decodes latin1 (iso-8859-1) text. Each byte is one character.
+pub fn latin1_decode(data: &[u8]) -> String {
+    data.iter().copied().map(char::from).collect()
+}
+
+/// Encodes the string as latin1 (iso-8859-1).
+pub fn latin1_encode(s: &str) -> Result<Vec<u8>> {
+    s.chars()
+        .map(|c| {
+            u8::try_from(c).map_err(|_| {
+                Error::Msg(
+                    format!("Character '{c}' cannot be encoded in latin1.")
+                        .into(),
+                )
+            })
+        })
+        .collect()
+}
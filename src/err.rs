@@ -14,6 +14,15 @@ pub enum Error {
     ParserExpected(&'static str),
     #[error("{}", .0)]
     Msg(Cow<'static, str>),
+    /// Exceeded limit of the evaluation, contains the `#make` call chain.
+    #[error("{}", .0)]
+    Limit(String),
+    #[error("{} (at line {}, column {})", .err, .line, .col)]
+    At {
+        line: usize,
+        col: usize,
+        err: Box<Error>,
+    },
     #[error("Command {} failed with stderr:\n{}", .cmd, .stderr)]
     CommandUnsuccessful { cmd: String, stderr: String },
     #[error(transparent)]
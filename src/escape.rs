@@ -0,0 +1,83 @@
+use std::fmt::Write;
+
+/// Escapes the string so that it can be placed inside json string (the
+/// quotes are not added).
+pub fn json_escape(s: &str) -> String {
+    let res = serde_json::to_string(s).unwrap_or_default();
+    res[1..res.len() - 1].to_owned()
+}
+
+/// Creates quoted toml basic string.
+pub fn toml_string(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            '\x08' => res.push_str("\\b"),
+            '\x0c' => res.push_str("\\f"),
+            c if c.is_control() => _ = write!(res, "\\u{:04X}", c as u32),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// Creates double quoted yaml string.
+pub fn yaml_string(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            '\0' => res.push_str("\\0"),
+            c if c.is_control() => _ = write!(res, "\\x{:02X}", c as u32),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// Escapes the characters that have special meaning in xml.
+pub fn xml_escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Percent encodes all characters except the unreserved characters.
+pub fn url_encode(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'_'
+            | b'.'
+            | b'~' => res.push(b as char),
+            b => _ = write!(res, "%{b:02X}"),
+        }
+    }
+    res
+}
@@ -0,0 +1,142 @@
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use termal::printmcln;
+
+use crate::value::Value;
+
+/// Layer from which value of a variable may come. Layers declared first have
+/// higher precedence.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum VarSource {
+    Cli,
+    VarsFile,
+    Alias,
+    Config,
+    Internal,
+    Template,
+}
+
+/// Remembers all the values that were given to each variable in each layer
+/// so that the final resolution can be explained to the user.
+pub struct VarTrace {
+    use_color: bool,
+    vars: BTreeMap<String, Vec<(VarSource, String)>>,
+    undefined: HashSet<String>,
+}
+
+/// Remembers what was done with each file of the template and why.
+pub struct FileTrace {
+    use_color: bool,
+    files: RefCell<BTreeMap<PathBuf, (String, &'static str)>>,
+}
+
+impl Display for VarSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cli => f.write_str("cli"),
+            Self::VarsFile => f.write_str("vars-file"),
+            Self::Alias => f.write_str("alias"),
+            Self::Config => f.write_str("config"),
+            Self::Internal => f.write_str("internal"),
+            Self::Template => f.write_str("template"),
+        }
+    }
+}
+
+impl VarTrace {
+    pub fn new(use_color: bool) -> Self {
+        Self {
+            use_color,
+            vars: BTreeMap::new(),
+            undefined: HashSet::new(),
+        }
+    }
+
+    /// Records the variables from the layer `src`.
+    pub fn record<'a, I, K>(&mut self, src: VarSource, vars: I)
+    where
+        I: IntoIterator<Item = (K, &'a Value<'a>)>,
+        K: AsRef<str>,
+    {
+        for (k, v) in vars {
+            let layers = self.vars.entry(k.as_ref().to_owned()).or_default();
+            let v = serde_json::to_string(v).unwrap_or_else(|_| v.to_string());
+            layers.push((src, v));
+            layers.sort_by_key(|(s, _)| *s);
+        }
+    }
+
+    /// Records the variables that were explicitly undefined with `-U`.
+    pub fn undefine<'a, I>(&mut self, names: I)
+    where
+        I: IntoIterator<Item = &'a Cow<'a, str>>,
+    {
+        for n in names {
+            self.vars.entry(n.to_string()).or_default();
+            self.undefined.insert(n.to_string());
+        }
+    }
+
+    /// Prints the resolved variables together with their sources to the
+    /// stdout.
+    pub fn print(&self) {
+        let color = self.use_color;
+        printmcln!(color, "{'g}Variables:{'_}");
+
+        for (name, layers) in &self.vars {
+            let mut shadowed = layers.iter();
+            if self.undefined.contains(name) {
+                printmcln!(color, "  {'y}{name}{'_} undefined {'gr}[-U]{'_}");
+            } else if let Some((src, value)) = shadowed.next() {
+                printmcln!(
+                    color,
+                    "  {'y}{name}{'_} = {value} {'gr}[{src}]{'_}"
+                );
+            }
+
+            for (src, value) in shadowed {
+                printmcln!(
+                    color,
+                    "    {'gr}shadowed{'_} {value} {'gr}[{src}]{'_}"
+                );
+            }
+        }
+    }
+}
+
+impl FileTrace {
+    pub fn new(use_color: bool) -> Self {
+        Self {
+            use_color,
+            files: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Records that `action` was chosen for the file `path` because of
+    /// `reason`.
+    pub fn record<A>(&self, path: &Path, action: A, reason: &'static str)
+    where
+        A: Display,
+    {
+        self.files
+            .borrow_mut()
+            .insert(path.to_owned(), (action.to_string(), reason));
+    }
+
+    /// Prints the actions chosen for the files to the stdout.
+    pub fn print(&self) {
+        let color = self.use_color;
+        printmcln!(color, "{'g}Files:{'_}");
+
+        for (path, (action, reason)) in self.files.borrow().iter() {
+            let path = path.display();
+            printmcln!(color, "  {'y}{path}{'_} {action} {'gr}[{reason}]{'_}");
+        }
+    }
+}
@@ -0,0 +1,130 @@
+/// Glob pattern that is matched against paths relative to the template.
+///
+/// `*` matches any characters except `/`, `?` matches one character,
+/// `[abc]`, `[a-z]` and `[!abc]` match one character from (or not from) the
+/// set and `**` as whole component matches any number of components.
+/// Pattern that starts with `!` is negated.
+pub struct Glob {
+    parts: Vec<Vec<char>>,
+    pub negated: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(p) => (true, p),
+            None => (false, pattern),
+        };
+
+        Self {
+            parts: pattern
+                .split('/')
+                .filter(|p| !p.is_empty() && *p != ".")
+                .map(|p| p.chars().collect())
+                .collect(),
+            negated,
+        }
+    }
+
+    /// Checks whether the key in `files` is glob pattern and not just path.
+    pub fn is_glob(key: &str) -> bool {
+        key.starts_with('!') || key.contains(['*', '?', '['])
+    }
+
+    /// Checks whether the relative path matches the pattern. Negation is not
+    /// considered.
+    pub fn matches(&self, path: &str) -> bool {
+        let path: Vec<Vec<char>> = path
+            .split('/')
+            .filter(|p| !p.is_empty() && *p != ".")
+            .map(|p| p.chars().collect())
+            .collect();
+        match_parts(&self.parts, &path)
+    }
+}
+
+fn match_parts(pat: &[Vec<char>], path: &[Vec<char>]) -> bool {
+    match pat.split_first() {
+        None => path.is_empty(),
+        Some((p, rest)) if p[..] == ['*', '*'] => {
+            (0..=path.len()).any(|i| match_parts(rest, &path[i..]))
+        }
+        Some((p, rest)) => path.split_first().is_some_and(|(c, path)| {
+            match_name(p, c) && match_parts(rest, path)
+        }),
+    }
+}
+
+/// Matches single path component.
+fn match_name(pat: &[char], name: &[char]) -> bool {
+    let mut p = 0;
+    let mut n = 0;
+    // position after the last `*` and the position in name that it matches
+    let mut star = None;
+
+    while n < name.len() {
+        let step = match pat.get(p) {
+            Some('*') => {
+                p += 1;
+                star = Some((p, n));
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => match class(&pat[p..], name[n]) {
+                Some((true, len)) => Some(len),
+                Some((false, _)) => None,
+                // unclosed `[` is just character
+                None => (name[n] == '[').then_some(1),
+            },
+            Some(&c) => (c == name[n]).then_some(1),
+            None => None,
+        };
+
+        match (step, star) {
+            (Some(len), _) => {
+                p += len;
+                n += 1;
+            }
+            (None, Some((sp, sn))) => {
+                p = sp;
+                n = sn + 1;
+                star = Some((sp, n));
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pat[p..].iter().all(|c| *c == '*')
+}
+
+/// Matches character class at the start of `pat`. Returns whether `c`
+/// matches and the length of the class. Returns `None` if the class is not
+/// closed.
+fn class(pat: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pat.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        match *pat.get(i)? {
+            ']' if !first => break,
+            a if pat.get(i + 1) == Some(&'-')
+                && pat.get(i + 2).is_some_and(|b| *b != ']') =>
+            {
+                matched |= (a..=pat[i + 2]).contains(&c);
+                i += 3;
+            }
+            a => {
+                matched |= a == c;
+                i += 1;
+            }
+        }
+        first = false;
+    }
+
+    Some((matched != negated, i + 1))
+}
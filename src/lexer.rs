@@ -1,8 +1,22 @@
-use std::fmt::{Display, Write};
+use std::{
+    fmt::{Display, Write},
+    mem,
+};
 
-use crate::err::{Error, Result};
+use crate::{
+    ast::{Expr, Literal},
+    err::{Error, Result},
+    parser::parse,
+    text::dedent,
+};
+
+/// The newest syntax version supported by this version of makemake.
+pub const LATEST_SYNTAX: u32 = 2;
+
+/// Syntax version from which unknown escape sequences are errors and the
+/// escapes `\" \$ \0 \x.. \u{..}` are supported.
+pub const STRICT_ESCAPES: u32 = 2;
 
-#[derive(Debug)]
 pub enum Token {
     CloseBracket,
     Question,
@@ -13,10 +27,21 @@ pub enum Token {
     NullCheck,
     Ident(String),
     Literal(String),
+    Number(String),
+    /// Double quoted literal with `${...}` interpolation.
+    Interpolated(Expr),
     Pound,
     Comma,
     Assign,
     Minus,
+    Plus,
+    Star,
+    Slash,
+    Percent,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
 }
 
 impl Display for Token {
@@ -31,10 +56,20 @@ impl Display for Token {
             Self::NullCheck => f.write_str("??"),
             Self::Ident(i) => f.write_str(i),
             Self::Literal(l) => f.write_str(l),
+            Self::Number(n) => f.write_str(n),
+            Self::Interpolated(_) => f.write_str("\"...\""),
             Self::Pound => f.write_char('#'),
             Self::Comma => f.write_char(','),
             Self::Assign => f.write_char('='),
             Self::Minus => f.write_char('-'),
+            Self::Plus => f.write_char('+'),
+            Self::Star => f.write_char('*'),
+            Self::Slash => f.write_char('/'),
+            Self::Percent => f.write_char('%'),
+            Self::Less => f.write_char('<'),
+            Self::LessEq => f.write_str("<="),
+            Self::Greater => f.write_char('>'),
+            Self::GreaterEq => f.write_str(">="),
         }
     }
 }
@@ -45,6 +80,7 @@ where
 {
     cur: Option<char>,
     data: &'a mut I,
+    syntax: u32,
 }
 
 impl<I> Iterator for Lexer<'_, I>
@@ -58,14 +94,16 @@ where
     }
 }
 
-impl<'a, I> From<&'a mut I> for Lexer<'a, I>
+impl<'a, I> Lexer<'a, I>
 where
     I: Iterator<Item = Result<char>>,
 {
-    fn from(value: &'a mut I) -> Self {
+    /// Creates lexer for the given version of the template syntax.
+    pub fn new(data: &'a mut I, syntax: u32) -> Self {
         Self {
             cur: None,
-            data: value,
+            data,
+            syntax,
         }
     }
 }
@@ -133,7 +171,43 @@ where
                 self.cur = None;
                 Ok(Some(Token::Minus))
             }
+            Some('+') => {
+                self.cur = None;
+                Ok(Some(Token::Plus))
+            }
+            Some('*') => {
+                self.cur = None;
+                Ok(Some(Token::Star))
+            }
+            Some('/') => {
+                self.cur = None;
+                Ok(Some(Token::Slash))
+            }
+            Some('%') => {
+                self.cur = None;
+                Ok(Some(Token::Percent))
+            }
+            Some('<') => {
+                self.next_chr()?;
+                if matches!(self.cur, Some('=')) {
+                    self.cur = None;
+                    Ok(Some(Token::LessEq))
+                } else {
+                    Ok(Some(Token::Less))
+                }
+            }
+            Some('>') => {
+                self.next_chr()?;
+                if matches!(self.cur, Some('=')) {
+                    self.cur = None;
+                    Ok(Some(Token::GreaterEq))
+                } else {
+                    Ok(Some(Token::Greater))
+                }
+            }
             Some('\'') => self.read_literal(),
+            Some('"') => self.read_interpolated(),
+            Some(a) if a.is_ascii_digit() => self.read_number(),
             Some(a) if a.is_alphabetic() || a == '_' => self.read_ident(),
             None => Ok(None),
             Some(c) => Err(Error::LexerUnexpected(c)),
@@ -149,7 +223,7 @@ where
         }
 
         while let Some(c) = self.next_chr()? {
-            if !c.is_alphanumeric() && c != '_' {
+            if !c.is_alphanumeric() && c != '_' && c != '.' {
                 break;
             }
             ident.push(c);
@@ -158,42 +232,191 @@ where
         Ok(Some(Token::Ident(ident)))
     }
 
+    fn read_number(&mut self) -> Result<Option<Token>> {
+        let mut num = String::new();
+
+        // should be always true
+        if let Some(c) = self.cur {
+            num.push(c);
+        }
+
+        while let Some(c) = self.next_chr()? {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            num.push(c);
+        }
+
+        Ok(Some(Token::Number(num)))
+    }
+
     fn read_literal(&mut self) -> Result<Option<Token>> {
         let mut lit = String::new();
 
-        let mut success = false;
-        while let Some(c) = self.next_chr()? {
+        // `''` is empty literal and `'''` starts multi-line literal
+        self.next_chr()?;
+        if self.cur == Some('\'') {
+            if self.next_chr()? == Some('\'') {
+                return self.read_multiline();
+            }
+            return Ok(Some(Token::Literal(lit)));
+        }
+
+        while let Some(c) = self.cur {
             match c {
                 '\'' => {
-                    success = true;
                     self.next_chr()?;
-                    break;
+                    return Ok(Some(Token::Literal(lit)));
                 }
                 '\\' => lit.push(self.escape()?),
                 _ => lit.push(c),
             }
+            self.next_chr()?;
         }
 
-        if !success {
-            return Err(Error::LexerExpect("`'` to close the literal"));
+        Err(Error::LexerExpect("`'` to close the literal"))
+    }
+
+    /// Reads literal enclosed in `'''`. The first line is skipped if it is
+    /// empty and common indentation is removed from all lines.
+    fn read_multiline(&mut self) -> Result<Option<Token>> {
+        let mut raw = String::new();
+        let mut quotes = 0;
+        while let Some(c) = self.next_chr()? {
+            match c {
+                '\'' if quotes == 2 => {
+                    raw.truncate(raw.len() - 2);
+                    self.next_chr()?;
+                    let text = dedent(skip_empty_line(&raw));
+                    return Ok(Some(Token::Literal(unescape(
+                        &text,
+                        self.syntax,
+                    )?)));
+                }
+                '\'' => quotes += 1,
+                '\\' => {
+                    quotes = 0;
+                    raw.push(c);
+                    if let Some(c) = self.next_chr()? {
+                        raw.push(c);
+                    }
+                    continue;
+                }
+                _ => quotes = 0,
+            }
+            raw.push(c);
         }
 
-        Ok(Some(Token::Literal(lit)))
+        Err(Error::LexerExpect("`'''` to close the multi-line literal"))
     }
 
-    fn escape(&mut self) -> Result<char> {
-        if let Some(c) = self.next_chr()? {
+    /// Reads double quoted literal. Expressions in `${...}` are parsed and
+    /// concatenated with the text.
+    fn read_interpolated(&mut self) -> Result<Option<Token>> {
+        let mut res = Expr::None;
+        let mut lit = String::new();
+
+        self.next_chr()?;
+        while let Some(c) = self.cur {
             match c {
-                'n' => Ok('\n'),
-                'r' => Ok('\r'),
-                't' => Ok('\t'),
-                '\\' => Ok('\\'),
-                '\'' => Ok('\''),
-                c => Ok(c),
+                '"' => {
+                    self.next_chr()?;
+                    res.concat(Literal::new(lit).into());
+                    return Ok(Some(Token::Interpolated(res)));
+                }
+                '\\' => lit.push(self.escape()?),
+                '$' => {
+                    if self.next_chr()? != Some('{') {
+                        lit.push('$');
+                        continue;
+                    }
+                    res.concat(Literal::new(mem::take(&mut lit)).into());
+                    res.concat(parse(self.data, self.syntax)?);
+                }
+                _ => lit.push(c),
             }
-        } else {
-            Err(Error::LexerExpect("escape sequence"))
+            self.next_chr()?;
+        }
+
+        Err(Error::LexerExpect("`\"` to close the literal"))
+    }
+
+    fn escape(&mut self) -> Result<char> {
+        let Some(c) = self.next_chr()? else {
+            return Err(Error::LexerExpect("escape sequence"));
+        };
+
+        match c {
+            'n' => Ok('\n'),
+            'r' => Ok('\r'),
+            't' => Ok('\t'),
+            '\\' => Ok('\\'),
+            '\'' => Ok('\''),
+            // older templates may rely on escaping any character
+            c if self.syntax < STRICT_ESCAPES => Ok(c),
+            '"' => Ok('"'),
+            '$' => Ok('$'),
+            '0' => Ok('\0'),
+            'x' => self.hex_escape(),
+            'u' => self.unicode_escape(),
+            c => Err(Error::Msg(
+                format!("Unknown escape sequence '\\{c}'.").into(),
+            )),
+        }
+    }
+
+    /// Reads escape `\xHH` after the `x`.
+    fn hex_escape(&mut self) -> Result<char> {
+        let mut code = 0;
+        for _ in 0..2 {
+            let d = self
+                .next_chr()?
+                .and_then(|c| c.to_digit(16))
+                .ok_or(Error::LexerExpect("two hex digits after '\\x'"))?;
+            code = code * 16 + d;
+        }
+
+        if code > 0x7f {
+            return Err(Error::Msg(
+                format!(
+                    "Escape '\\x{code:02X}' is out of ascii range, use \
+                    '\\u{{{code:X}}}' instead."
+                )
+                .into(),
+            ));
+        }
+        Ok(char::from(code as u8))
+    }
+
+    /// Reads escape `\u{XXXX}` after the `u`.
+    fn unicode_escape(&mut self) -> Result<char> {
+        if self.next_chr()? != Some('{') {
+            return Err(Error::LexerExpect("'{' after '\\u'"));
         }
+
+        let mut code = 0;
+        let mut len = 0;
+        loop {
+            match self.next_chr()?.map(|c| (c, c.to_digit(16))) {
+                Some(('}', _)) if len > 0 => break,
+                Some((_, Some(d))) if len < 6 => {
+                    code = code * 16 + d;
+                    len += 1;
+                }
+                _ => {
+                    return Err(Error::LexerExpect(
+                        "1 to 6 hex digits in '\\u{...}'",
+                    ))
+                }
+            }
+        }
+
+        char::from_u32(code).ok_or_else(|| {
+            Error::Msg(
+                format!("'\\u{{{code:X}}}' is not valid unicode character.")
+                    .into(),
+            )
+        })
     }
 
     fn next_chr(&mut self) -> Result<Option<char>> {
@@ -201,3 +424,63 @@ where
         Ok(self.cur)
     }
 }
+
+/// Characters decoded from utf-8 bytes as input for the lexer. The part of
+/// the data that was not read is available after parsing.
+pub struct ByteChars<'a>(pub &'a [u8]);
+
+impl Iterator for ByteChars<'_> {
+    type Item = Result<char>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let &first = self.0.first()?;
+        let len = match first {
+            0..=0x7f => {
+                self.0 = &self.0[1..];
+                return Some(Ok(char::from(first)));
+            }
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 0,
+        };
+
+        let chr = self
+            .0
+            .get(..len)
+            .and_then(|b| std::str::from_utf8(b).ok())
+            .and_then(|s| s.chars().next());
+        match chr {
+            Some(c) => {
+                self.0 = &self.0[len..];
+                Some(Ok(c))
+            }
+            None => Some(Err(Error::Msg(
+                "Expression contains invalid utf-8.".into(),
+            ))),
+        }
+    }
+}
+
+/// Skips the first line if it contains only whitespace.
+fn skip_empty_line(s: &str) -> &str {
+    match s.split_once('\n') {
+        Some((first, rest)) if first.trim().is_empty() => rest,
+        _ => s,
+    }
+}
+
+/// Resolves the escape sequences in `s`.
+fn unescape(s: &str, syntax: u32) -> Result<String> {
+    let mut chars = s.chars().map(Ok);
+    let mut lexer = Lexer::new(&mut chars, syntax);
+    let mut res = String::with_capacity(s.len());
+    while let Some(c) = lexer.next_chr()? {
+        if c == '\\' {
+            res.push(lexer.escape()?);
+        } else {
+            res.push(c);
+        }
+    }
+    Ok(res)
+}
@@ -6,7 +6,8 @@ use maker::{copy_dir, create_template, load_template};
 use pareg::Pareg;
 use std::{
     borrow::Cow,
-    fs::{read_dir, remove_dir_all},
+    collections::HashMap,
+    fs::{read_dir, remove_dir_all, File},
     io::{stderr, stdin, stdout, IsTerminal, Write},
     mem,
     path::{Path, PathBuf},
@@ -14,16 +15,34 @@ use std::{
 };
 use termal::{eprintmcln, printmcln};
 
-use crate::{config::Alias, err::Error};
+use crate::{
+    config::Alias,
+    err::Error,
+    explain::{FileTrace, VarSource, VarTrace},
+    lexer::LATEST_SYNTAX,
+    value::Value,
+};
 
 mod args;
 mod ast;
+mod budget;
+mod cache;
 mod commander;
 mod config;
+mod encoding;
 mod err;
+mod escape;
+mod explain;
+mod glob;
 mod lexer;
 mod maker;
 mod parser;
+mod paths;
+mod random;
+mod scope;
+mod text;
+mod value;
+mod version;
 mod writer;
 
 fn main() -> ExitCode {
@@ -38,6 +57,10 @@ fn main() -> ExitCode {
 
 fn start() -> Result<()> {
     let mut args = Args::parse(Pareg::args())?;
+    // `load` reads the files itself so that it can trace them.
+    if !matches!(args.action, Action::Load) {
+        load_vars_files(&mut args, None)?;
+    }
 
     // Do what the arguments specify
     match mem::take(&mut args.action) {
@@ -123,13 +146,27 @@ fn configure(args: Args) -> Result<()> {
 /// Loads template with the name `src` to the directory `dest`. `vars` can
 /// add/override variables in the template config file.
 fn load(mut args: Args) -> Result<()> {
+    let mut trace = args.explain_vars.then(|| VarTrace::new(args.use_color()));
+    let explain = args.explain_files.then(|| FileTrace::new(args.use_color()));
+
+    if let Some(t) = &mut trace {
+        t.record(VarSource::Cli, &args.vars);
+        t.undefine(&args.undefine);
+    }
+    load_vars_files(&mut args, trace.as_mut())?;
+
     let mut name: Cow<str> = args.template.into();
     let conf = load_config()?;
 
     if let Some(a) = conf.aliases.get(name.as_ref()) {
         name = a.template.as_str().into();
+        if let Some(t) = &mut trace {
+            t.record(VarSource::Alias, &a.vars);
+        }
         for (k, v) in &a.vars {
-            args.vars.entry(k.clone()).or_insert(v.clone());
+            if !args.undefine.contains(k) {
+                args.vars.entry(k.clone()).or_insert(v.clone());
+            }
         }
     }
 
@@ -157,11 +194,46 @@ fn load(mut args: Args) -> Result<()> {
         return Ok(());
     }
 
+    if let Some(t) = &mut trace {
+        t.record(VarSource::Config, &conf.vars);
+    }
     for (k, v) in conf.vars {
-        args.vars.entry(k).or_insert(v);
+        if !args.undefine.contains(&k) {
+            args.vars.entry(k).or_insert(v);
+        }
     }
 
-    load_template(template, args.directory.as_ref(), args.vars)
+    load_template(
+        template,
+        args.directory.as_ref(),
+        args.vars,
+        &args.undefine,
+        trace,
+        explain,
+        args.verbose,
+    )
+}
+
+/// Adds the variables from the files given with `--vars-file`. Variables
+/// set with `-D` or `-U` have priority.
+fn load_vars_files(
+    args: &mut Args,
+    mut trace: Option<&mut VarTrace>,
+) -> Result<()> {
+    for f in mem::take(&mut args.vars_files) {
+        let vars: HashMap<Cow<str>, Value> =
+            serde_json::from_reader(File::open(f)?)?;
+        if let Some(t) = &mut trace {
+            t.record(VarSource::VarsFile, &vars);
+        }
+        for (k, v) in vars {
+            if !args.undefine.contains(&k) {
+                args.vars.entry(k).or_insert(v);
+            }
+        }
+    }
+
+    Ok(())
 }
 
 /// Deletes template with the name `name`
@@ -224,10 +296,9 @@ fn list() -> Result<()> {
     for (n, a) in conf.aliases {
         print!("{n} : {}", a.template);
         for (n, v) in a.vars {
-            if v.is_empty() {
-                print!(" -D{n}",);
-            } else {
-                print!(" -D{n}={v}");
+            match v {
+                Value::Str(v) if v.is_empty() => print!(" -D{n}"),
+                v => print!(" -D{n}={v}"),
             }
         }
         println!();
@@ -302,6 +373,7 @@ fn version(args: Args) {
     printmcln!(
         args.use_color(),
         "makemake v{v}
+Syntax version: {LATEST_SYNTAX}
 Author: {signature}{'_}
 Exe path: {exe}
 "
@@ -346,6 +418,34 @@ Version {}
   {'y}-D{'w}<variable name>{'gr}[=value]{'_}
     Defines/redefines a variable.
 
+  {'y}-U{'w}<variable name>{'_}
+  {'y}-U {'w}<variable name>{'_}
+    Undefines the variable for this load, even if it is set by alias, global
+    configuration, internal variables or the template.
+
+  {'y}--vars-file {'w}<path to json file>{'_}
+    Defines variables from json file with object where keys are variable
+    names. Values may be also lists and objects. Variables set with {'y}-D{'_}
+    have priority.
+
+  {'y}--seed {'w}<seed>{'_}
+    Seeds the random functions ({'y}#uuid{'_}, {'y}#randomHex{'_}, ...) so that
+    the output is reproducible. Same as {'y}-D_SEED={'w}<seed>{'_}.
+
+  {'y}-v  --verbose{'_}
+    When loading, print statistics about the evaluation of the template
+    (e.g. how many {'y}#make{'_} calls were reused from cache).
+
+  {'y}--explain-vars{'_}
+    When loading, print the final value of each variable, the layer from
+    which it comes and the values that it shadows.
+
+  {'y}--explain{'_}
+    When loading, print the action taken for each file of the template and
+    for each directory in {'y}files{'_} and why it was chosen (listed in
+    {'y}files{'_}, {'y}defaultAction{'_} or detected from the contents for
+    {'y}Auto{'_}).
+
   {'y}-e  --edit {'w}<template name>{'_}
     Loads template source to this directory. If the directory is destination
     directory and it doesn't exist, it will be created.
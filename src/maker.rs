@@ -1,18 +1,36 @@
 use std::{
     borrow::Cow,
-    collections::HashMap,
-    fmt::Write,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::Display,
     fs::{self, create_dir_all, read_dir, read_link, File},
-    io::{BufReader, BufWriter},
+    mem,
     os::unix::fs::symlink,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
-use serde::{Deserialize, Serialize};
-use utf8_chars::BufReadCharsExt;
+use memchr::{memchr, memmem};
+use serde::{
+    de::{MapAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 
 use crate::{
-    commander::run_command, err::Result, parser::parse, writer::ToFmtWrite,
+    budget::{Budget, Limits},
+    cache::MakeCache,
+    commander::run_command,
+    encoding::{latin1_decode, latin1_encode},
+    err::{Error, Result},
+    explain::{FileTrace, VarSource, VarTrace},
+    glob::Glob,
+    lexer::{ByteChars, LATEST_SYNTAX},
+    parser::parse,
+    random::Random,
+    scope::Scope,
+    value::Value,
+    version::Version,
+    writer::{BudgetWriter, ByteWrite, CrlfWriter, FakeWriter, ToFmtWrite},
 };
 
 #[derive(Serialize, Deserialize)]
@@ -24,11 +42,38 @@ struct MakeConfig<'a> {
     #[serde(default, rename = "expandVariables")]
     expand_variables: bool,
     #[serde(default)]
-    files: HashMap<PathBuf, MakeInfo>,
+    files: Files,
+    #[serde(default)]
+    vars: HashMap<Cow<'a, str>, Value<'a>>,
+    #[serde(default = "default_syntax_version", rename = "syntaxVersion")]
+    syntax_version: u32,
+    #[serde(default, rename = "minMakemakeVersion")]
+    min_makemake_version: Option<String>,
     #[serde(default)]
-    vars: HashMap<Cow<'a, str>, Cow<'a, str>>,
+    limits: Limits,
+    #[serde(default = "default_action", rename = "defaultAction")]
+    default_action: MakeType,
     #[serde(skip)]
     template_path: PathBuf,
+    #[serde(skip)]
+    dst_path: PathBuf,
+    #[serde(skip)]
+    random: Random,
+    #[serde(skip)]
+    budget: Budget,
+    #[serde(skip)]
+    cache: MakeCache,
+    #[serde(skip)]
+    explain: Option<FileTrace>,
+}
+
+/// Actions for the files in the template. Keys are either paths relative to
+/// the template or glob patterns.
+#[derive(Default)]
+struct Files {
+    paths: HashMap<PathBuf, MakeInfo>,
+    /// Glob patterns in the order in which they are in the config.
+    globs: Vec<(String, Glob, MakeInfo)>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -47,18 +92,188 @@ enum MakeType {
     Ignore,
 }
 
+impl Files {
+    /// Gets the info for the path relative to the template. Exact path has
+    /// priority (also if the key looks like pattern), otherwise the last
+    /// matching glob pattern is used. If the last matching pattern is
+    /// negated, the path has no info.
+    fn get(&self, path: &Path) -> Option<&MakeInfo> {
+        // the root of the template is not in `files`
+        if path.as_os_str().is_empty() {
+            return None;
+        }
+        if let Some(info) = self.paths.get(path) {
+            return Some(info);
+        }
+        // keys that look like patterns may also be exact paths
+        // (e.g. `pages/[id].js`)
+        let exact = self
+            .globs
+            .iter()
+            .rev()
+            .find(|(k, g, _)| !g.negated && Path::new(k) == path);
+        if let Some((_, _, info)) = exact {
+            return Some(info);
+        }
+
+        let path = path.to_str()?;
+        let (_, glob, info) =
+            self.globs.iter().rev().find(|(_, g, _)| g.matches(path))?;
+        (!glob.negated).then_some(info)
+    }
+
+    /// Iterates over the infos of all the paths and patterns.
+    fn infos_mut(&mut self) -> impl Iterator<Item = &mut MakeInfo> {
+        self.paths
+            .values_mut()
+            .chain(self.globs.iter_mut().map(|(_, _, i)| i))
+    }
+
+    /// Gets the variables of the path and of all the directories that
+    /// contain it. The variables of the path itself are last.
+    fn vars(&self, path: &Path) -> Vec<&FileVars> {
+        let mut res: Vec<_> = path
+            .ancestors()
+            .filter_map(|p| match self.get(p) {
+                Some(MakeInfo::Info(i)) if !i.vars.is_empty() => Some(&i.vars),
+                _ => None,
+            })
+            .collect();
+        res.reverse();
+        res
+    }
+}
+
+impl<'de> Deserialize<'de> for Files {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct FilesVisitor;
+
+        impl<'de> Visitor<'de> for FilesVisitor {
+            type Value = Files;
+
+            fn expecting(
+                &self,
+                f: &mut std::fmt::Formatter,
+            ) -> std::fmt::Result {
+                f.write_str("map of paths or glob patterns to file actions")
+            }
+
+            fn visit_map<A>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Files, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut res = Files::default();
+                // the order of the entries is preserved for the globs
+                while let Some((k, v)) = map.next_entry::<String, _>()? {
+                    if Glob::is_glob(&k) {
+                        res.globs.push((k.clone(), Glob::new(&k), v));
+                    } else {
+                        res.paths.insert(k.into(), v);
+                    }
+                }
+                Ok(res)
+            }
+        }
+
+        deserializer.deserialize_map(FilesVisitor)
+    }
+}
+
+impl Serialize for Files {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer
+            .serialize_map(Some(self.paths.len() + self.globs.len()))?;
+        for (k, v) in &self.paths {
+            map.serialize_entry(k, v)?;
+        }
+        for (k, _, v) in &self.globs {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+impl Display for MakeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => f.write_str("Auto"),
+            Self::Copy => f.write_str("Copy"),
+            Self::Make => f.write_str("Make"),
+            Self::Ignore => f.write_str("Ignore"),
+        }
+    }
+}
+
+#[derive(
+    Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default,
+)]
+pub enum Encoding {
+    #[default]
+    #[serde(rename = "utf-8", alias = "utf8")]
+    Utf8,
+    #[serde(rename = "latin1", alias = "iso-8859-1")]
+    Latin1,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 struct FileInfo {
+    /// `defaultAction` is used if not set.
     #[serde(default)]
-    action: MakeType,
+    action: Option<MakeType>,
     #[serde(default)]
     name: String,
+    #[serde(default)]
+    encoding: Encoding,
+    #[serde(default)]
+    condition: Option<String>,
+    #[serde(default)]
+    vars: FileVars,
 }
 
+/// Variables of file. `None` undefines the variable.
+type FileVars = HashMap<Cow<'static, str>, Option<Value<'static>>>;
+
 #[derive(Copy, Clone)]
 pub struct ExpandContext<'a> {
-    pub vars: &'a HashMap<Cow<'a, str>, Cow<'a, str>>,
+    pub vars: Scope<'a>,
     pub template_dir: &'a Path,
+    /// Variables that are resolved only when they are first used and they
+    /// are not in `vars`.
+    pub lazy: Option<&'a LazyVars<'a>>,
+    pub random: &'a Random,
+    /// Version of the template syntax.
+    pub syntax: u32,
+    pub budget: &'a Budget,
+    pub cache: &'a MakeCache,
+    /// Encoding of the expanded file. Files used with `#make` are expanded
+    /// with the same encoding.
+    pub encoding: Encoding,
+}
+
+/// Template variables that are expanded on demand so that they may reference
+/// each other.
+pub struct LazyVars<'a> {
+    vars: &'a HashMap<Cow<'a, str>, Value<'a>>,
+    template_dir: &'a Path,
+    random: &'a Random,
+    syntax: u32,
+    budget: &'a Budget,
+    cache: &'a MakeCache,
+    raw: HashMap<Cow<'a, str>, Value<'a>>,
+    resolved: RefCell<HashMap<String, Value<'static>>>,
+    stack: RefCell<Vec<String>>,
 }
 
 pub fn create_template<P1, P2>(src: P1, out: P2) -> Result<()>
@@ -72,7 +287,11 @@ where
 pub fn load_template<'a, P1, P2>(
     src: P1,
     dst: P2,
-    vars: HashMap<Cow<'a, str>, Cow<'a, str>>,
+    vars: HashMap<Cow<'a, str>, Value<'a>>,
+    undefine: &HashSet<Cow<str>>,
+    mut trace: Option<VarTrace>,
+    explain: Option<FileTrace>,
+    verbose: bool,
 ) -> Result<()>
 where
     P1: AsRef<Path>,
@@ -91,13 +310,19 @@ where
         let conf = File::open(conf)?;
         let mut conf: MakeConfig = serde_json::from_reader(conf)?;
         conf.template_path = src.to_owned();
+        conf.dst_path = dst.to_owned();
+        conf.explain = explain;
+        conf.check_version()?;
 
         create_dir_all(dst)?;
-        conf.init(vars, dst)?;
+        conf.init(vars, undefine, dst, trace.as_mut())?;
+        if let Some(t) = trace {
+            t.print();
+        }
 
         let run_cmd = |c: &String| {
             let mut cmd = String::new();
-            expand(conf.context(), &mut c.chars().map(Ok), &mut cmd)?;
+            expand(conf.context(), c.as_bytes(), &mut cmd)?;
             run_command(&cmd, src, dst, &conf.vars)
         };
 
@@ -106,64 +331,151 @@ where
         conf.make_dir(src, dst)?;
 
         conf.post_command.as_ref().map(run_cmd).unwrap_or(Ok(()))?;
+
+        if let Some(e) = &conf.explain {
+            e.print();
+        }
+
+        if verbose {
+            let (hits, misses) = conf.cache.stats();
+            println!("'#make' cache: {hits} hits, {misses} misses");
+        }
         Ok(())
     } else {
+        if let Some(t) = trace {
+            t.print();
+        }
         copy_dir(src, dst)
     }
 }
 
 impl<'a> MakeConfig<'a> {
-    fn load_internal_variables<P>(
-        vars: &mut HashMap<Cow<'a, str>, Cow<'a, str>>,
-        dst: P,
-    ) where
+    fn internal_variables<P>(dst: P) -> HashMap<Cow<'a, str>, Value<'a>>
+    where
         P: AsRef<Path>,
     {
+        let mut vars = HashMap::new();
+
         if let Some(dname) = dst.as_ref().file_name() {
-            vars.entry("_PDIR".into())
-                .or_insert(dname.to_string_lossy().into_owned().into());
+            vars.insert(
+                "_PDIR".into(),
+                dname.to_string_lossy().into_owned().into(),
+            );
         }
 
         #[cfg(target_os = "linux")]
         {
-            vars.entry("_LINUX".into()).or_insert("linux".into());
-            vars.entry("_OS".into()).or_insert("linux".into());
+            vars.insert("_LINUX".into(), "linux".into());
+            vars.insert("_OS".into(), "linux".into());
         }
         #[cfg(target_os = "windows")]
         {
-            vars.entry("_WINDOWS".into()).or_insert("windows".into());
-            vars.entry("_OS".into()).or_insert("windows".into());
+            vars.insert("_WINDOWS".into(), "windows".into());
+            vars.insert("_OS".into(), "windows".into());
         }
         #[cfg(target_os = "macos")]
         {
-            vars.entry("_MACOS".into()).or_insert("macos".into());
-            vars.entry("_OS".into()).or_insert("macos".into());
+            vars.insert("_MACOS".into(), "macos".into());
+            vars.insert("_OS".into(), "macos".into());
         }
         #[cfg(target_os = "ios")]
         {
-            vars.entry("_IOS".into()).or_insert("ios".into());
-            vars.entry("_OS".into()).or_insert("ios".into());
+            vars.insert("_IOS".into(), "ios".into());
+            vars.insert("_OS".into(), "ios".into());
         }
         #[cfg(target_os = "freebsd")]
         {
-            vars.entry("_FREEBSD".into()).or_insert("freebsd".into());
-            vars.entry("_OS".into()).or_insert("freebsd".into());
+            vars.insert("_FREEBSD".into(), "freebsd".into());
+            vars.insert("_OS".into(), "freebsd".into());
+        }
+
+        vars
+    }
+
+    /// Checks that the template can be loaded by this version of makemake.
+    fn check_version(&self) -> Result<()> {
+        let current = env!("CARGO_PKG_VERSION");
+        if let Some(min) = &self.min_makemake_version {
+            if min.parse::<Version>()? > current.parse()? {
+                return Err(Error::Msg(
+                    format!(
+                        "The template requires makemake {min} or newer, but \
+                        this is makemake {current}. Update makemake to load \
+                        the template."
+                    )
+                    .into(),
+                ));
+            }
         }
+
+        if !(1..=LATEST_SYNTAX).contains(&self.syntax_version) {
+            return Err(Error::Msg(
+                format!(
+                    "The template uses syntax version {}, but makemake \
+                    {current} supports only syntax versions 1 to \
+                    {LATEST_SYNTAX}.{}",
+                    self.syntax_version,
+                    if self.syntax_version > LATEST_SYNTAX {
+                        " Update makemake to load the template."
+                    } else {
+                        ""
+                    }
+                )
+                .into(),
+            ));
+        }
+
+        Ok(())
     }
 
     fn init<P>(
         &mut self,
-        mut vars: HashMap<Cow<'a, str>, Cow<'a, str>>,
+        mut vars: HashMap<Cow<'a, str>, Value<'a>>,
+        undefine: &HashSet<Cow<str>>,
         dst: P,
+        mut trace: Option<&mut VarTrace>,
     ) -> Result<()>
     where
         P: AsRef<Path>,
     {
-        Self::load_internal_variables(&mut vars, dst);
+        let internal = Self::internal_variables(dst);
+        if let Some(t) = &mut trace {
+            t.record(VarSource::Internal, &internal);
+        }
+        for (k, v) in internal {
+            vars.entry(k).or_insert(v);
+        }
+        vars.retain(|k, _| !undefine.contains(k.as_ref()));
+
+        // undefined variables must not be visible to the dependent template
+        // variables
+        if let Some(t) = &mut trace {
+            let undefined =
+                self.vars.iter().filter(|(k, _)| undefine.contains(*k));
+            t.record(VarSource::Template, undefined);
+        }
+        self.vars.retain(|k, _| !undefine.contains(k.as_ref()));
+
+        // variables of files are layered on the template variables, so the
+        // variables from outside of the template have priority over them
+        for info in self.files.infos_mut() {
+            if let MakeInfo::Info(i) = info {
+                i.vars.retain(|k, _| {
+                    !vars.contains_key(k) && !undefine.contains(k)
+                });
+            }
+        }
+
+        let seed = vars.get("_SEED").or_else(|| self.vars.get("_SEED"));
+        self.random = Random::new(seed.map(|s| s.to_string()).as_deref());
+        self.budget = Budget::new(self.limits);
 
         if self.expand_variables {
             self.expand_variables(&vars)?;
         }
+        if let Some(t) = &mut trace {
+            t.record(VarSource::Template, &self.vars);
+        }
         self.vars.extend(vars);
 
         Ok(())
@@ -171,28 +483,44 @@ impl<'a> MakeConfig<'a> {
 
     fn context(&self) -> ExpandContext<'_> {
         ExpandContext {
-            vars: &self.vars,
+            vars: Scope::Root(&self.vars),
             template_dir: &self.template_path,
+            lazy: None,
+            random: &self.random,
+            syntax: self.syntax_version,
+            budget: &self.budget,
+            cache: &self.cache,
+            encoding: Encoding::default(),
         }
     }
 
     fn expand_variables(
         &mut self,
-        vars: &HashMap<Cow<'a, str>, Cow<'a, str>>,
+        vars: &HashMap<Cow<'a, str>, Value<'a>>,
     ) -> Result<()> {
-        for v in self.vars.values_mut() {
-            let mut res = String::new();
-            expand(
-                ExpandContext {
-                    vars,
-                    template_dir: &self.template_path,
-                },
-                &mut v.chars().map(Ok),
-                &mut res,
-            )?;
-            *v = res.into();
+        let lazy = LazyVars {
+            vars,
+            template_dir: &self.template_path,
+            random: &self.random,
+            syntax: self.syntax_version,
+            budget: &self.budget,
+            cache: &self.cache,
+            raw: mem::take(&mut self.vars),
+            resolved: RefCell::new(HashMap::new()),
+            stack: RefCell::new(vec![]),
+        };
+
+        for k in lazy.raw.keys() {
+            lazy.get(k)?;
         }
 
+        self.vars = lazy
+            .resolved
+            .into_inner()
+            .into_iter()
+            .map(|(k, v)| (k.into(), v))
+            .collect();
+
         Ok(())
     }
 
@@ -204,14 +532,20 @@ impl<'a> MakeConfig<'a> {
             vec![(rsrc.as_ref().into(), rdst.as_ref().into())];
 
         while let Some((src, dst)) = dirs.pop() {
-            let meta = src.symlink_metadata()?;
-            if meta.is_symlink() {
-                self.make_symlink_name(src, &rsrc, dst.into_owned())?;
-            } else if meta.is_file() {
-                self.make_file_name(src, &rsrc, dst.into_owned())?;
-            } else if meta.is_dir() {
-                self.make_dir_name(&mut dirs, src, &rsrc, dst.into_owned())?;
-            }
+            let layers = self.files.vars(src.strip_prefix(&rsrc)?);
+            with_scope(self.context(), &layers, &mut |ctx| {
+                let dst = dst.to_path_buf();
+                let meta = src.symlink_metadata()?;
+                if meta.is_symlink() {
+                    self.make_symlink_name(ctx, &src, &rsrc, dst)
+                } else if meta.is_file() {
+                    self.make_file_name(ctx, &src, &rsrc, dst)
+                } else if meta.is_dir() {
+                    self.make_dir_name(ctx, &mut dirs, &src, &rsrc, dst)
+                } else {
+                    Ok(())
+                }
+            })?;
         }
 
         Ok(())
@@ -219,6 +553,7 @@ impl<'a> MakeConfig<'a> {
 
     fn make_symlink_name<P1, P2>(
         &self,
+        ctx: ExpandContext,
         src: P1,
         rsrc: P2,
         mut dst: PathBuf,
@@ -234,10 +569,16 @@ impl<'a> MakeConfig<'a> {
         // let srel = diff_paths(&src, &rsrc).unwrap();
         let srel = src.strip_prefix(rsrc)?;
 
+        if !self.expand_file_name(ctx, &mut dst)? {
+            return Ok(());
+        }
+
         if let Some(info) = self.files.get(srel) {
             let action = match info {
                 MakeInfo::TypeOnly(a) => *a,
-                MakeInfo::Info(i) => self.make_name(i, &mut dst)?,
+                MakeInfo::Info(i) => {
+                    self.make_name(ctx, i, MakeType::Auto, &mut dst)?.0
+                }
             };
 
             match action {
@@ -257,6 +598,7 @@ impl<'a> MakeConfig<'a> {
 
     fn make_file_name<P1, P2>(
         &self,
+        ctx: ExpandContext,
         src: P1,
         rsrc: P2,
         mut dst: PathBuf,
@@ -272,25 +614,51 @@ impl<'a> MakeConfig<'a> {
         // let srel = diff_paths(&src, &rsrc).unwrap();
         let srel = src.strip_prefix(rsrc)?;
 
-        if let Some(info) = self.files.get(srel) {
-            let action = match info {
-                MakeInfo::TypeOnly(a) => *a,
-                MakeInfo::Info(i) => self.make_name(i, &mut dst)?,
-            };
+        if !self.expand_file_name(ctx, &mut dst)? {
+            if let Some(e) = &self.explain {
+                e.record(srel, MakeType::Ignore, "empty name");
+            }
+            return Ok(());
+        }
 
-            match action {
-                MakeType::Copy | MakeType::Auto => _ = fs::copy(src, dst)?,
-                MakeType::Make => {
-                    let mut src = BufReader::new(File::open(src)?);
-                    self.expand(
-                        &mut src.chars().map(|c| Ok(c?)),
-                        &mut ToFmtWrite(BufWriter::new(File::create(dst)?)),
-                    )?;
-                }
-                MakeType::Ignore => {}
+        let (action, encoding, mut reason) = match self.files.get(srel) {
+            Some(MakeInfo::TypeOnly(a)) => (*a, Encoding::default(), "files"),
+            Some(MakeInfo::Info(i)) => {
+                let (action, reason) =
+                    self.make_name(ctx, i, self.default_action, &mut dst)?;
+                (action, i.encoding, reason)
             }
+            None => (self.default_action, Encoding::default(), "default"),
+        };
+
+        let mut data = None;
+        let action = if let MakeType::Auto = action {
+            let d = fs::read(src)?;
+            let (action, why) = auto_action(&d, encoding);
+            reason = why;
+            data = Some(d);
+            action
         } else {
-            fs::copy(src, dst)?;
+            action
+        };
+
+        if let Some(e) = &self.explain {
+            e.record(srel, action, reason);
+        }
+
+        match action {
+            MakeType::Copy | MakeType::Auto => _ = fs::copy(src, dst)?,
+            MakeType::Make => {
+                let data = match data {
+                    Some(d) => d,
+                    None => fs::read(src)?,
+                };
+                // the output is written only if the expansion succeeds
+                let data =
+                    expand_data(ExpandContext { encoding, ..ctx }, &data)?;
+                fs::write(dst, data)?;
+            }
+            MakeType::Ignore => {}
         }
 
         Ok(())
@@ -298,6 +666,7 @@ impl<'a> MakeConfig<'a> {
 
     fn make_dir_name<P1, P2>(
         &self,
+        ctx: ExpandContext,
         dirs: &mut Vec<(Cow<Path>, Cow<Path>)>,
         src: P1,
         rsrc: P2,
@@ -314,11 +683,25 @@ impl<'a> MakeConfig<'a> {
         // let srel = diff_paths(&src, &rsrc).unwrap();
         let srel = src.strip_prefix(rsrc)?;
 
+        // the root is the destination directory given by the user
+        let root = srel.as_os_str().is_empty();
+        if !root && !self.expand_file_name(ctx, &mut dst)? {
+            if let Some(e) = &self.explain {
+                e.record(srel, MakeType::Ignore, "empty name");
+            }
+            return Ok(());
+        }
+
         if let Some(info) = self.files.get(srel) {
-            let action = match info {
-                MakeInfo::TypeOnly(a) => *a,
-                MakeInfo::Info(i) => self.make_name(i, &mut dst)?,
+            let (action, reason) = match info {
+                MakeInfo::TypeOnly(a) => (*a, "files"),
+                MakeInfo::Info(i) => {
+                    self.make_name(ctx, i, MakeType::Auto, &mut dst)?
+                }
             };
+            if let Some(e) = &self.explain {
+                e.record(srel, action, reason);
+            }
             match action {
                 MakeType::Copy => copy_dir(src, dst)?,
                 MakeType::Auto | MakeType::Make => {
@@ -333,59 +716,330 @@ impl<'a> MakeConfig<'a> {
         Ok(())
     }
 
-    fn expand<I, W>(&self, src: &mut I, dst: &mut W) -> Result<()>
-    where
-        I: Iterator<Item = Result<char>>,
-        W: Write,
-    {
-        expand(self.context(), src, dst)
+    /// Expands the expressions in the file name of `path`. Returns `false`
+    /// if the name expands to empty string.
+    fn expand_file_name(
+        &self,
+        ctx: ExpandContext,
+        path: &mut PathBuf,
+    ) -> Result<bool> {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return Ok(true);
+        };
+        if !name.contains("${") {
+            return Ok(true);
+        }
+
+        let mut res = String::new();
+        expand(ctx, name.as_bytes(), &mut res)?;
+        if res.is_empty() {
+            return Ok(false);
+        }
+        self.rename(path, &res)?;
+        Ok(true)
     }
 
+    /// Gets the action for the file and renames it. `default` is used if
+    /// the info has no action. Returns also the reason for the action.
     fn make_name(
         &self,
+        ctx: ExpandContext,
         info: &FileInfo,
+        default: MakeType,
         path: &mut PathBuf,
-    ) -> Result<MakeType> {
+    ) -> Result<(MakeType, &'static str)> {
+        let (action, reason) = match info.action {
+            Some(a) => (a, "files"),
+            None => (default, "default"),
+        };
+        if let MakeType::Ignore = action {
+            return Ok((MakeType::Ignore, reason));
+        }
+
+        if let Some(cond) = &info.condition {
+            if !self.check_condition(ctx, cond)? {
+                return Ok((MakeType::Ignore, "condition"));
+            }
+        }
+
         if !info.name.is_empty() {
             let mut name = String::new();
-            self.expand(&mut info.name.chars().map(Ok), &mut name)?;
+            expand(ctx, info.name.as_bytes(), &mut name)?;
             if name.is_empty() {
-                Ok(MakeType::Ignore)
-            } else {
-                path.set_file_name(name);
-                Ok(info.action)
+                return Ok((MakeType::Ignore, "empty name"));
+            }
+            self.rename(path, &name)?;
+        }
+
+        Ok((action, reason))
+    }
+
+    /// Evaluates the condition of file. The condition is true if the
+    /// expression is defined.
+    fn check_condition(&self, ctx: ExpandContext, cond: &str) -> Result<bool> {
+        let mut chars = ByteChars(cond.as_bytes());
+        let expr = parse(&mut chars, self.syntax_version).map_err(|e| {
+            error_at(cond.as_bytes(), cond.len() - chars.0.len(), e)
+        })?;
+        if !chars.0.is_empty() {
+            return Err(Error::Msg(
+                format!("Unexpected '}}' in condition '{cond}'.").into(),
+            ));
+        }
+        expr.eval(&mut FakeWriter, ctx)
+    }
+
+    /// Replaces the file name of `path` with `name`. The name may be
+    /// relative path, missing directories are created. The result must be
+    /// in the destination directory.
+    fn rename(&self, path: &mut PathBuf, name: &str) -> Result<()> {
+        let parent = path.parent().unwrap_or(&self.dst_path);
+        let mut res: Vec<_> =
+            parent.strip_prefix(&self.dst_path)?.components().collect();
+
+        for c in Path::new(name).components() {
+            match c {
+                Component::Normal(_) => res.push(c),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if res.pop().is_none() {
+                        return Err(Error::Msg(
+                            format!(
+                                "Name '{name}' leads outside of the \
+                                destination directory."
+                            )
+                            .into(),
+                        ));
+                    }
+                }
+                Component::RootDir | Component::Prefix(_) => {
+                    return Err(Error::Msg(
+                        format!("Name '{name}' must be relative path.").into(),
+                    ))
+                }
+            }
+        }
+
+        let last = Path::new(name).components().next_back();
+        if !matches!(last, Some(Component::Normal(_))) {
+            return Err(Error::Msg(
+                format!("Name '{name}' doesn't lead to file.").into(),
+            ));
+        }
+
+        *path = self.dst_path.join(res.iter().collect::<PathBuf>());
+        // the name may lead to directory that doesn't exist yet
+        if Path::new(name).components().count() > 1 {
+            if let Some(p) = path.parent() {
+                create_dir_all(p)?;
             }
-        } else {
-            Ok(info.action)
         }
+        Ok(())
     }
 }
 
-pub fn expand<I, W>(ctx: ExpandContext, src: &mut I, dst: &mut W) -> Result<()>
+impl<'a> ExpandContext<'a> {
+    /// Gets the value of the variable from `vars` or from `lazy`.
+    pub fn get(&self, name: &str) -> Result<Option<Cow<'a, Value<'a>>>> {
+        Ok(match (self.vars.get(name), self.lazy) {
+            (Some(v), _) => v.map(Cow::Borrowed),
+            (None, Some(l)) => l.get(name)?.map(Cow::Owned),
+            (None, None) => None,
+        })
+    }
+}
+
+impl LazyVars<'_> {
+    /// Gets the expanded value of the variable. The variable is expanded
+    /// only the first time it is requested.
+    pub fn get(&self, name: &str) -> Result<Option<Value<'static>>> {
+        if let Some(v) = self.resolved.borrow().get(name) {
+            return Ok(Some(v.clone()));
+        }
+
+        let Some(value) = self.raw.get(name) else {
+            return Ok(None);
+        };
+
+        if let Some(pos) = self.stack.borrow().iter().position(|v| v == name) {
+            let cycle = self.stack.borrow()[pos..].join(" -> ");
+            return Err(Error::Msg(
+                format!("Cycle in template variables: {cycle} -> {name}")
+                    .into(),
+            ));
+        }
+
+        self.stack.borrow_mut().push(name.to_owned());
+        let ctx = ExpandContext {
+            vars: Scope::Root(self.vars),
+            template_dir: self.template_dir,
+            lazy: Some(self),
+            random: self.random,
+            syntax: self.syntax,
+            budget: self.budget,
+            cache: self.cache,
+            encoding: Encoding::default(),
+        };
+        let res = value.try_map_str(&mut |v| {
+            let mut res = String::new();
+            expand(ctx, v.as_bytes(), &mut res)?;
+            Ok::<_, Error>(res)
+        });
+        self.stack.borrow_mut().pop();
+        let res = res?;
+
+        self.resolved
+            .borrow_mut()
+            .insert(name.to_owned(), res.clone());
+        Ok(Some(res))
+    }
+}
+
+/// Expands the contents of file with the encoding given by `ctx`.
+pub fn expand_data(ctx: ExpandContext, data: &[u8]) -> Result<Vec<u8>> {
+    match ctx.encoding {
+        Encoding::Utf8 => {
+            let mut res = ToFmtWrite(Vec::with_capacity(data.len()));
+            expand(ctx, data, &mut res)?;
+            Ok(res.0)
+        }
+        Encoding::Latin1 => {
+            let mut res = String::with_capacity(data.len());
+            expand(ctx, latin1_decode(data).as_bytes(), &mut res)?;
+            latin1_encode(&res)
+        }
+    }
+}
+
+/// Expands the expressions in `src` and writes the result to `dst`. Bytes
+/// outside of the expressions are copied as they are, so only the
+/// expressions must be valid utf-8.
+pub fn expand<W>(ctx: ExpandContext, src: &[u8], dst: &mut W) -> Result<()>
 where
-    I: Iterator<Item = Result<char>>,
-    W: Write,
+    W: ByteWrite,
 {
-    while let Some(c) = src.next().transpose()? {
-        if c != '$' {
-            dst.write_char(c)?;
-            continue;
-        }
+    // output of nested `#make` is counted as it is written to its buffer
+    let mut dst = BudgetWriter {
+        inner: dst,
+        budget: ctx.budget,
+    };
+    expand_to(ctx, src, &mut dst).map_err(|e| {
+        // the writer cannot tell why it failed
+        ctx.budget.check_total_output().err().unwrap_or(e)
+    })
+}
 
-        if let Some(c) = src.next().transpose()? {
-            if c != '{' {
-                dst.write_char('$')?;
-                dst.write_char(c)?;
+fn expand_to<W>(ctx: ExpandContext, src: &[u8], dst: &mut W) -> Result<()>
+where
+    W: ByteWrite,
+{
+    // new lines written by expressions match the line endings of the source
+    let crlf =
+        memchr(b'\n', src).is_some_and(|i| i > 0 && src[i - 1] == b'\r');
+
+    let mut rest = src;
+    let mut pos = 0;
+    // text without `${` is copied at once
+    while let Some(i) = memchr(b'$', &rest[pos..]).map(|i| i + pos) {
+        match rest.get(i + 1) {
+            Some(b'{') => dst.write_bytes(&rest[..i])?,
+            // `$` and the following character are copied as they are
+            Some(_) => {
+                pos = i + 2;
                 continue;
             }
-
-            parse(src)?.eval(dst, ctx)?;
+            None => {
+                dst.write_bytes(&rest[..i])?;
+                return Ok(());
+            }
         }
+
+        ctx.budget.step()?;
+        let start = src.len() - rest.len() + i + 1;
+        let mut chars = ByteChars(&rest[i + 2..]);
+        let expr = parse(&mut chars, ctx.syntax)
+            .map_err(|e| error_at(src, src.len() - chars.0.len(), e))?;
+        rest = chars.0;
+        pos = 0;
+        let res = if crlf {
+            let mut dst = CrlfWriter {
+                inner: dst,
+                cr: false,
+            };
+            expr.eval(&mut dst, ctx)
+        } else {
+            expr.eval(dst, ctx)
+        };
+        // errors from `#make` already have position in the other file and
+        // errors of limits show the call chain instead
+        res.map_err(|e| match e {
+            Error::At { .. } | Error::Limit(_) => e,
+            e => error_at(src, start, e),
+        })?;
     }
 
+    dst.write_bytes(rest)?;
     Ok(())
 }
 
+/// Adds the position of the character before `pos` in `src` to the error.
+fn error_at(src: &[u8], pos: usize, err: Error) -> Error {
+    let read = &src[..pos];
+    let line = read.rsplit(|&b| b == b'\n').next().unwrap_or_default();
+    Error::At {
+        line: read.iter().filter(|&&b| b == b'\n').count() + 1,
+        col: String::from_utf8_lossy(line).chars().count(),
+        err: Box::new(err),
+    }
+}
+
+fn default_syntax_version() -> u32 {
+    1
+}
+
+/// Calls `f` with context where the variables from `layers` are in nested
+/// scopes. The last layer has the highest priority.
+fn with_scope<R>(
+    ctx: ExpandContext,
+    layers: &[&FileVars],
+    f: &mut dyn FnMut(ExpandContext) -> R,
+) -> R {
+    match layers.split_first() {
+        None => f(ctx),
+        Some((vars, rest)) => with_scope(
+            ExpandContext {
+                vars: Scope::Nested {
+                    parent: &ctx.vars,
+                    vars,
+                },
+                ..ctx
+            },
+            rest,
+            f,
+        ),
+    }
+}
+
+fn default_action() -> MakeType {
+    MakeType::Copy
+}
+
+/// Decides what to do with file that has the action `Auto`. Text files with
+/// expressions are expanded and all other files are copied. Returns also the
+/// reason for the decision.
+fn auto_action(data: &[u8], encoding: Encoding) -> (MakeType, &'static str) {
+    let text = memchr(0, data).is_none()
+        && (matches!(encoding, Encoding::Latin1)
+            || std::str::from_utf8(data).is_ok());
+    if !text {
+        (MakeType::Copy, "auto: binary")
+    } else if memmem::find(data, b"${").is_some() {
+        (MakeType::Make, "auto: contains '${'")
+    } else {
+        (MakeType::Copy, "auto: no expressions")
+    }
+}
+
 pub fn copy_dir<P1, P2>(rsrc: P1, rdst: P2) -> Result<()>
 where
     P1: AsRef<Path>,
@@ -1,7 +1,8 @@
-use std::collections::HashMap;
-
 use crate::{
-    ast::{Call, Condition, Equals, Expr, Literal, NullCheck, Variable},
+    ast::{
+        Arg, Arith, ArithOp, Call, CmpOp, Compare, Condition, Equals, Expr,
+        Literal, NullCheck, Switch, Variable,
+    },
     err::{Error, Result},
     lexer::{Lexer, Token},
 };
@@ -12,13 +13,14 @@ where
 {
     lexer: I,
     cur: Option<Token>,
+    peeked: Option<Option<Token>>,
 }
 
-pub fn parse<I>(data: &mut I) -> Result<Expr>
+pub fn parse<I>(data: &mut I, syntax: u32) -> Result<Expr>
 where
     I: Iterator<Item = Result<char>>,
 {
-    let lexer: Lexer<I> = data.into();
+    let lexer = Lexer::new(data, syntax);
     let mut parser = Parser::new(lexer);
     parser.parse()
 }
@@ -28,7 +30,11 @@ where
     I: Iterator<Item = Result<Token>>,
 {
     pub fn new(lexer: I) -> Self {
-        Self { lexer, cur: None }
+        Self {
+            lexer,
+            cur: None,
+            peeked: None,
+        }
     }
 
     pub fn parse(&mut self) -> Result<Expr> {
@@ -50,11 +56,14 @@ where
             match t {
                 Token::Question => return self.condition(res),
                 Token::NullCheck => return self.null_check(res),
-                Token::OpenParen => res.concat(self.paren()?),
-                Token::Pound => res.concat(self.call()?),
                 Token::Equals => res = self.equals(res)?,
-                Token::Ident(i) => res.concat(Variable::new(i).into()),
-                Token::Literal(l) => res.concat(Literal::new(l).into()),
+                Token::Less => res = self.compare(res, CmpOp::Less)?,
+                Token::LessEq => res = self.compare(res, CmpOp::LessEq)?,
+                Token::Greater => res = self.compare(res, CmpOp::Greater)?,
+                Token::GreaterEq => {
+                    res = self.compare(res, CmpOp::GreaterEq)?
+                }
+                t if Self::is_operand(&t) => res.concat(self.arith(t)?),
                 _ => {
                     self.cur = Some(t);
                     break;
@@ -66,6 +75,92 @@ where
         Ok(res)
     }
 
+    fn is_operand(t: &Token) -> bool {
+        matches!(
+            t,
+            Token::OpenParen
+                | Token::Pound
+                | Token::Ident(_)
+                | Token::Literal(_)
+                | Token::Number(_)
+                | Token::Interpolated(_)
+                | Token::Minus
+        )
+    }
+
+    /// Parses `+` and `-` starting with the token `first`.
+    fn arith(&mut self, first: Token) -> Result<Expr> {
+        let mut res = self.term(first)?;
+
+        loop {
+            self.get_tok()?;
+            let op = match self.cur {
+                Some(Token::Plus) => ArithOp::Add,
+                Some(Token::Minus) => ArithOp::Sub,
+                _ => return Ok(res),
+            };
+            let r = self.operand(Self::term)?;
+            res = Arith::new(op, res, r).into();
+        }
+    }
+
+    /// Parses `*`, `/` and `%` starting with the token `first`.
+    fn term(&mut self, first: Token) -> Result<Expr> {
+        let mut res = self.unary(first)?;
+
+        loop {
+            self.get_tok()?;
+            let op = match self.cur {
+                Some(Token::Star) => ArithOp::Mul,
+                Some(Token::Slash) => ArithOp::Div,
+                Some(Token::Percent) => ArithOp::Rem,
+                _ => return Ok(res),
+            };
+            let r = self.operand(Self::unary)?;
+            res = Arith::new(op, res, r).into();
+        }
+    }
+
+    /// Parses single value or value with unary `-`.
+    fn unary(&mut self, first: Token) -> Result<Expr> {
+        match first {
+            Token::Minus => {
+                let value = self.operand(Self::unary)?;
+                Ok(Arith::new(
+                    ArithOp::Sub,
+                    Literal::new("0".into()).into(),
+                    value,
+                )
+                .into())
+            }
+            Token::OpenParen => self.paren(),
+            Token::Pound => self.call(),
+            Token::Ident(i) => Ok(Variable::new(i).into()),
+            Token::Literal(l) | Token::Number(l) => Ok(Literal::new(l).into()),
+            Token::Interpolated(e) => Ok(e),
+            t => {
+                self.cur = Some(t);
+                Err(Error::ParserExpected("operand"))
+            }
+        }
+    }
+
+    /// Skips the current operator and parses the following operand with
+    /// `f`.
+    fn operand<F>(&mut self, f: F) -> Result<Expr>
+    where
+        F: FnOnce(&mut Self, Token) -> Result<Expr>,
+    {
+        self.next_tok()?;
+        match self.cur.take() {
+            Some(t) if Self::is_operand(&t) => f(self, t),
+            t => {
+                self.cur = t;
+                Err(Error::ParserExpected("operand"))
+            }
+        }
+    }
+
     fn paren(&mut self) -> Result<Expr> {
         let res = self.expr()?;
         if !matches!(self.cur, Some(Token::CloseParen)) {
@@ -82,16 +177,12 @@ where
         let mut res = Expr::None;
 
         while let Some(t) = self.cur.take() {
-            match t {
-                Token::OpenParen => res.concat(self.paren()?),
-                Token::Ident(i) => res.concat(Variable::new(i).into()),
-                Token::Literal(l) => res.concat(Literal::new(l).into()),
-                _ => {
-                    self.cur = Some(t);
-                    break;
-                }
+            if !Self::is_operand(&t) {
+                self.cur = Some(t);
+                break;
             }
-            self.next_tok()?;
+            res.concat(self.arith(t)?);
+            self.get_tok()?;
         }
 
         Ok(res)
@@ -102,6 +193,11 @@ where
         Ok(Equals::new(l, r).into())
     }
 
+    fn compare(&mut self, l: Expr, op: CmpOp) -> Result<Expr> {
+        let r = self.concat()?;
+        Ok(Compare::new(op, l, r).into())
+    }
+
     fn condition(&mut self, cond: Expr) -> Result<Expr> {
         let success = self.expr()?;
 
@@ -135,56 +231,128 @@ where
         }
         self.next_tok()?;
 
-        let file = self.expr()?;
+        if ident == "switch" {
+            return self.switch();
+        }
 
-        let mut define = HashMap::new();
-        let mut undefine = Vec::new();
+        let mut args = vec![];
+        self.get_tok()?;
+        if !matches!(self.cur, Some(Token::CloseParen)) {
+            loop {
+                args.push(self.arg()?);
+                self.get_tok()?;
+                if !matches!(self.cur, Some(Token::Comma)) {
+                    break;
+                }
+                self.next_tok()?;
+                if matches!(self.cur, Some(Token::CloseParen)) {
+                    break;
+                }
+            }
+        }
+
+        if !matches!(self.cur, Some(Token::CloseParen)) {
+            return Err(Error::ParserExpected("')'"));
+        }
+        self.next_tok()?;
+
+        Ok(Call::new(Variable::new(ident), args).into())
+    }
+
+    /// Parses function argument. It may be expression, variable definition
+    /// (`name = value`) or variable undefinition (`-name`).
+    fn arg(&mut self) -> Result<Arg> {
+        let undef = matches!(self.cur, Some(Token::Minus))
+            && matches!(self.peek()?, Some(Token::Ident(_)));
+        let def = matches!(self.cur, Some(Token::Ident(_)))
+            && matches!(self.peek()?, Some(Token::Assign));
+
+        if !undef && !def {
+            return Ok(Arg::Value(self.expr()?));
+        }
+
+        if undef {
+            self.next_tok()?;
+        }
+        let Some(Token::Ident(ident)) = self.cur.take() else {
+            return Err(Error::ParserExpected("Identifer in arguments."));
+        };
+        if ident.contains('.') {
+            return Err(Error::ParserExpected(
+                "Identifier without members in arguments.",
+            ));
+        }
+
+        if undef {
+            return Ok(Arg::Undefine(Variable::new(ident)));
+        }
 
+        // skip the `=`
+        self.next_tok()?;
+        self.next_tok()?;
+        let value = self.expr()?;
+        Ok(Arg::Define(Variable::new(ident), value))
+    }
+
+    fn switch(&mut self) -> Result<Expr> {
+        let value = self.expr()?;
+
+        let mut cases = vec![];
+        let mut default = None;
+
+        self.get_tok()?;
         while matches!(self.cur, Some(Token::Comma)) {
             self.next_tok()?;
             if matches!(self.cur, Some(Token::CloseParen)) {
                 break;
             }
 
-            let mut undef = false;
-            if matches!(self.cur, Some(Token::Minus)) {
-                undef = true;
-                self.next_tok()?;
-            }
-
-            let Some(Token::Ident(ident)) = self.cur.take() else {
-                return Err(Error::ParserExpected("Identifer in arguments."));
-            };
-
-            let mut value = Expr::None;
+            let case = self.expr()?;
             self.get_tok()?;
-            if matches!(self.cur, Some(Token::Assign)) {
-                self.next_tok()?;
-                value = self.expr()?;
+            if !matches!(self.cur, Some(Token::Colon)) {
+                return Err(Error::ParserExpected("':' after switch case"));
             }
+            self.next_tok()?;
+
+            let branch = self.expr()?;
             self.get_tok()?;
 
-            if undef {
-                undefine.push(Variable::new(ident));
-            } else {
-                define.insert(Variable::new(ident), value);
+            match case {
+                Expr::Variable(v) if v.name() == "default" => {
+                    if default.is_some() {
+                        return Err(Error::Msg(
+                            "Switch may have only one default branch.".into(),
+                        ));
+                    }
+                    default = Some(branch);
+                }
+                _ => cases.push((case, branch)),
             }
         }
 
-        self.get_tok()?;
         if !matches!(self.cur, Some(Token::CloseParen)) {
             return Err(Error::ParserExpected("')'"));
         }
         self.next_tok()?;
 
-        Ok(Call::new(Variable::new(ident), file, define, undefine).into())
+        Ok(Switch::new(value, cases, default).into())
     }
 
     fn next_tok(&mut self) -> Result<()> {
-        self.cur = self.lexer.next().transpose()?;
+        self.cur = match self.peeked.take() {
+            Some(t) => t,
+            None => self.lexer.next().transpose()?,
+        };
         Ok(())
     }
 
+    fn peek(&mut self) -> Result<Option<&Token>> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.lexer.next().transpose()?);
+        }
+        Ok(self.peeked.as_ref().and_then(|t| t.as_ref()))
+    }
+
     fn get_tok(&mut self) -> Result<()> {
         if self.cur.is_none() {
             self.next_tok()
@@ -0,0 +1,126 @@
+use crate::err::{Error, Result};
+
+/// Gets the last component of the path.
+pub fn basename(path: &str) -> String {
+    let p = path.trim_end_matches('/');
+    if p.is_empty() {
+        return if path.is_empty() { "" } else { "/" }.to_owned();
+    }
+    p.rsplit('/').next().unwrap_or(p).to_owned()
+}
+
+/// Gets the path without its last component.
+pub fn dirname(path: &str) -> String {
+    let p = path.trim_end_matches('/');
+    match p.rsplit_once('/') {
+        Some((d, _)) => {
+            let d = d.trim_end_matches('/');
+            if d.is_empty() { "/" } else { d }.to_owned()
+        }
+        None if p.is_empty() && !path.is_empty() => "/".to_owned(),
+        None => ".".to_owned(),
+    }
+}
+
+/// Joins the paths with `/`. Empty parts are skipped.
+pub fn join_path<'a, I>(parts: I) -> String
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut res = String::new();
+    for p in parts {
+        if p.is_empty() {
+            continue;
+        }
+        if !res.is_empty() && !res.ends_with('/') {
+            res.push('/');
+        }
+        if res.is_empty() {
+            res.push_str(p);
+        } else {
+            res.push_str(p.trim_start_matches('/'));
+        }
+    }
+    res
+}
+
+/// Gets the extension of the file (without the `.`). Returns `None` if the
+/// file has no extension.
+pub fn extension(path: &str) -> Option<&str> {
+    let name = path.trim_end_matches('/').rsplit('/').next()?;
+    match name.rsplit_once('.') {
+        Some(("", _)) | None => None,
+        Some((_, ext)) => Some(ext),
+    }
+}
+
+/// Replaces the extension of the file. If `ext` is empty, the extension is
+/// removed.
+pub fn with_extension(path: &str, ext: &str) -> String {
+    let path = path.trim_end_matches('/');
+    let base = match extension(path) {
+        Some(e) => &path[..path.len() - e.len() - 1],
+        None => path,
+    };
+
+    let ext = ext.trim_start_matches('.');
+    if ext.is_empty() {
+        base.to_owned()
+    } else {
+        format!("{base}.{ext}")
+    }
+}
+
+/// Gets relative path that leads from the directory `from` to `to`.
+pub fn rel_path(from: &str, to: &str) -> Result<String> {
+    if from.starts_with('/') != to.starts_with('/') {
+        return Err(Error::Msg(
+            format!(
+                "Cannot get relative path between '{from}' and '{to}', only \
+                one of them is absolute."
+            )
+            .into(),
+        ));
+    }
+
+    let from = normalize(from)?;
+    let to = normalize(to)?;
+
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    if from[common..].contains(&"..") {
+        return Err(Error::Msg(
+            format!("Cannot get relative path from '{}'.", from.join("/"))
+                .into(),
+        ));
+    }
+    let res: Vec<_> = (common..from.len())
+        .map(|_| "..")
+        .chain(to[common..].iter().copied())
+        .collect();
+
+    if res.is_empty() {
+        Ok(".".to_owned())
+    } else {
+        Ok(res.join("/"))
+    }
+}
+
+/// Splits the path to components and resolves `.` and `..`.
+fn normalize(path: &str) -> Result<Vec<&str>> {
+    let mut res: Vec<&str> = vec![];
+    for c in path.split('/') {
+        match c {
+            "" | "." => {}
+            ".." if matches!(res.last(), Some(l) if *l != "..") => {
+                res.pop();
+            }
+            ".." if path.starts_with('/') => {
+                return Err(Error::Msg(
+                    format!("Path '{path}' leads outside of root.").into(),
+                ))
+            }
+            c => res.push(c),
+        }
+    }
+    Ok(res)
+}
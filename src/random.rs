@@ -0,0 +1,96 @@
+use std::cell::RefCell;
+
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha8Rng,
+};
+
+const ALNUM: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Source of random values for the template functions. It may be seeded so
+/// that the values are reproducible. ChaCha8 and the conversion of its
+/// output are fixed, so the same seed gives the same values on all platforms
+/// and versions of the dependencies.
+pub struct Random(RefCell<ChaCha8Rng>);
+
+impl Random {
+    /// Creates new random generator. If `seed` is `None`, the generator is
+    /// seeded from the operating system.
+    pub fn new(seed: Option<&str>) -> Self {
+        let rng = match seed {
+            Some(s) => ChaCha8Rng::from_seed(expand_seed(
+                s.parse().unwrap_or_else(|_| hash_seed(s)),
+            )),
+            None => ChaCha8Rng::from_os_rng(),
+        };
+        Self(RefCell::new(rng))
+    }
+
+    /// Generates `n` random hexadecimal digits.
+    pub fn hex(&self, n: usize) -> String {
+        let mut rng = self.0.borrow_mut();
+        (0..n)
+            .map(|_| char::from_digit(rng.next_u32() % 16, 16).unwrap())
+            .collect()
+    }
+
+    /// Generates `n` random letters and digits.
+    pub fn alnum(&self, n: usize) -> String {
+        let mut rng = self.0.borrow_mut();
+        let mut res = String::with_capacity(n);
+        while res.len() < n {
+            // values that are out of range are skipped so that all the
+            // characters have the same probability
+            if let Some(c) = ALNUM.get((rng.next_u32() >> 26) as usize) {
+                res.push(char::from(*c));
+            }
+        }
+        res
+    }
+
+    /// Generates random UUID (version 4).
+    pub fn uuid(&self) -> String {
+        let mut b = [0; 16];
+        self.0.borrow_mut().fill_bytes(&mut b);
+        b[6] = (b[6] & 0x0f) | 0x40;
+        b[8] = (b[8] & 0x3f) | 0x80;
+
+        let hex: String = b.iter().map(|b| format!("{b:02x}")).collect();
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// FNV-1a hash, so that the seed from string is same across platforms and
+/// versions.
+fn hash_seed(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Expands the seed to the seed of ChaCha8 with SplitMix64.
+fn expand_seed(mut seed: u64) -> [u8; 32] {
+    let mut res = [0; 32];
+    for chunk in res.chunks_exact_mut(8) {
+        seed = seed.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        chunk.copy_from_slice(&(z ^ (z >> 31)).to_le_bytes());
+    }
+    res
+}
@@ -0,0 +1,37 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::value::Value;
+
+/// Variables visible to the expressions. Nested scopes contain only the
+/// variables that they define or undefine and the other variables are taken
+/// from the parent scope, so creating nested scope doesn't copy all the
+/// variables.
+#[derive(Clone, Copy)]
+pub enum Scope<'a> {
+    Root(&'a HashMap<Cow<'a, str>, Value<'a>>),
+    Nested {
+        parent: &'a Scope<'a>,
+        /// `None` is variable that is undefined in this scope.
+        vars: &'a HashMap<Cow<'a, str>, Option<Value<'a>>>,
+    },
+}
+
+impl<'a> Scope<'a> {
+    /// Gets the variable from the closest scope that contains it. Returns
+    /// `Some(None)` if the variable is undefined in nested scope and `None`
+    /// if no scope contains the variable.
+    pub fn get(&self, name: &str) -> Option<Option<&'a Value<'a>>> {
+        let mut scope = *self;
+        loop {
+            match scope {
+                Self::Root(vars) => return vars.get(name).map(Some),
+                Self::Nested { parent, vars } => {
+                    if let Some(v) = vars.get(name) {
+                        return Some(v.as_ref());
+                    }
+                    scope = *parent;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+/// Adds `prefix` to the start of each line. Empty lines get the prefix
+/// without trailing whitespace.
+pub fn prefix_lines(text: &str, prefix: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        if line.trim_end_matches(['\n', '\r']).is_empty() {
+            res.push_str(prefix.trim_end());
+        } else {
+            res.push_str(prefix);
+        }
+        res.push_str(line);
+    }
+    res
+}
+
+/// Removes the common leading whitespace from all the non empty lines.
+pub fn dedent(text: &str) -> String {
+    let mut common: Option<&str> = None;
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        let ws =
+            &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+        common = Some(match common {
+            None => ws,
+            Some(c) => {
+                let len = c
+                    .bytes()
+                    .zip(ws.bytes())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                &c[..len]
+            }
+        });
+    }
+    let common = common.map_or(0, |c| c.len());
+
+    let mut res = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            res.push_str(line.trim_start_matches([' ', '\t']));
+        } else {
+            res.push_str(&line[common..]);
+        }
+    }
+    res
+}
+
+/// Wraps each line of the text so that it is at most `width` characters
+/// long. Words that are longer than `width` are not split.
+pub fn wrap(text: &str, width: usize) -> String {
+    let mut res = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let indent = &content[..content.len() - content.trim_start().len()];
+
+        let mut len = 0;
+        for word in content.split_whitespace() {
+            let wlen = word.chars().count();
+            if len == 0 {
+                res.push_str(indent);
+                len = indent.chars().count();
+            } else if len + 1 + wlen > width {
+                res.push('\n');
+                res.push_str(indent);
+                len = indent.chars().count();
+            } else {
+                res.push(' ');
+                len += 1;
+            }
+            res.push_str(word);
+            len += wlen;
+        }
+
+        res.push_str(&line[content.len()..]);
+    }
+    res
+}
+
+/// Gets the first line of the text without the newline.
+pub fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or("")
+}
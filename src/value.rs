@@ -0,0 +1,117 @@
+use std::{
+    borrow::Cow, collections::BTreeMap, convert::Infallible, fmt::Display,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Value of a variable. Plain strings are the most common, but variables may
+/// also contain lists and maps of other values.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value<'a> {
+    Str(Cow<'a, str>),
+    List(Vec<Value<'a>>),
+    Map(BTreeMap<String, Value<'a>>),
+}
+
+impl<'a> Value<'a> {
+    /// Gets the member of the value with the given key. Lists are indexed by
+    /// numbers and maps by their keys.
+    pub fn member(&self, key: &str) -> Option<&Value<'a>> {
+        match self {
+            Self::Str(_) => None,
+            Self::List(l) => key.parse::<usize>().ok().and_then(|i| l.get(i)),
+            Self::Map(m) => m.get(key),
+        }
+    }
+
+    /// Gets the value at the path of members separated with `.`.
+    pub fn path(&self, path: &str) -> Option<&Value<'a>> {
+        path.split('.').try_fold(self, |v, k| v.member(k))
+    }
+
+    /// Number of elements in list or map, or number of characters in string.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Str(s) => s.chars().count(),
+            Self::List(l) => l.len(),
+            Self::Map(m) => m.len(),
+        }
+    }
+
+    /// Gets the items over which the value can be iterated as pairs of key
+    /// and value. String is iterated as list with single item.
+    pub fn items(&self) -> Vec<(Cow<'_, str>, &Value<'a>)> {
+        match self {
+            Self::Str(_) => vec![("0".into(), self)],
+            Self::List(l) => l
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (i.to_string().into(), v))
+                .collect(),
+            Self::Map(m) => m.iter().map(|(k, v)| (k.into(), v)).collect(),
+        }
+    }
+
+    /// Copies the value so that it doesn't borrow anything.
+    pub fn to_static(&self) -> Value<'static> {
+        self.try_map_str(&mut |s| Ok::<_, Infallible>(s.to_owned()))
+            .unwrap_or_else(|e| match e {})
+    }
+
+    /// Applies `f` to all the strings in the value.
+    pub fn try_map_str<'b, F, E>(&self, f: &mut F) -> Result<Value<'b>, E>
+    where
+        F: FnMut(&str) -> Result<String, E>,
+    {
+        Ok(match self {
+            Self::Str(s) => Value::Str(f(s)?.into()),
+            Self::List(l) => Value::List(
+                l.iter()
+                    .map(|v| v.try_map_str(f))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Self::Map(m) => Value::Map(
+                m.iter()
+                    .map(|(k, v)| Ok((k.clone(), v.try_map_str(f)?)))
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+}
+
+impl Default for Value<'_> {
+    fn default() -> Self {
+        Self::Str("".into())
+    }
+}
+
+impl<'a> From<Cow<'a, str>> for Value<'a> {
+    fn from(value: Cow<'a, str>) -> Self {
+        Self::Str(value)
+    }
+}
+
+impl<'a> From<&'a str> for Value<'a> {
+    fn from(value: &'a str) -> Self {
+        Self::Str(value.into())
+    }
+}
+
+impl From<String> for Value<'_> {
+    fn from(value: String) -> Self {
+        Self::Str(value.into())
+    }
+}
+
+/// Strings are written as they are, lists and maps are written as json.
+impl Display for Value<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Str(s) => f.write_str(s),
+            _ => f.write_str(
+                &serde_json::to_string(self).map_err(|_| std::fmt::Error)?,
+            ),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
+
+use crate::err::Error;
+
+/// Semantic version. Missing minor and patch versions are zero, so `1.70` is
+/// the same as `1.70.0`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    pre: Vec<String>,
+}
+
+impl Version {
+    /// Increments the given part of the version (`major`, `minor` or
+    /// `patch`). The lower parts are reset to zero and the pre-release is
+    /// removed. Pre-release of the version that would be the result of the
+    /// bump is only released (e.g. `1.2.0-rc.1` bumps to `1.2.0` with
+    /// `minor`).
+    pub fn bump(&mut self, part: &str) -> Result<(), Error> {
+        let pre = !self.pre.is_empty();
+        match part {
+            "major" => {
+                if !pre || self.minor != 0 || self.patch != 0 {
+                    self.major += 1;
+                }
+                self.minor = 0;
+                self.patch = 0;
+            }
+            "minor" => {
+                if !pre || self.patch != 0 {
+                    self.minor += 1;
+                }
+                self.patch = 0;
+            }
+            "patch" if !pre => self.patch += 1,
+            "patch" => {}
+            _ => {
+                return Err(Error::Msg(
+                    format!(
+                        "Invalid version part '{part}'. Expected 'major', \
+                        'minor' or 'patch'."
+                    )
+                    .into(),
+                ))
+            }
+        }
+        self.pre.clear();
+        Ok(())
+    }
+}
+
+impl FromStr for Version {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err =
+            || Error::Msg(format!("'{s}' is not a valid version.").into());
+
+        let v = s.trim();
+        let v = v.strip_prefix('v').unwrap_or(v);
+        // build metadata doesn't affect the version
+        let v = v.split_once('+').map_or(v, |(v, _)| v);
+        let (v, pre) = v.split_once('-').unwrap_or((v, ""));
+
+        let mut nums = v.split('.').map(|n| n.parse::<u64>());
+        let major = nums.next().and_then(|n| n.ok()).ok_or_else(err)?;
+        let minor = nums.next().unwrap_or(Ok(0)).map_err(|_| err())?;
+        let patch = nums.next().unwrap_or(Ok(0)).map_err(|_| err())?;
+        if nums.next().is_some() {
+            return Err(err());
+        }
+
+        let pre: Vec<_> = if pre.is_empty() {
+            vec![]
+        } else {
+            pre.split('.').map(|p| p.to_owned()).collect()
+        };
+        if pre.iter().any(|p| p.is_empty()) {
+            return Err(err());
+        }
+
+        Ok(Self {
+            major,
+            minor,
+            patch,
+            pre,
+        })
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let num = (self.major, self.minor, self.patch).cmp(&(
+            other.major,
+            other.minor,
+            other.patch,
+        ));
+        if num != Ordering::Equal {
+            return num;
+        }
+
+        // version without pre-release is greater
+        match (self.pre.is_empty(), other.pre.is_empty()) {
+            (true, true) => return Ordering::Equal,
+            (true, false) => return Ordering::Greater,
+            (false, true) => return Ordering::Less,
+            _ => {}
+        }
+
+        for (a, b) in self.pre.iter().zip(&other.pre) {
+            let ord = match (a.parse::<u64>(), b.parse::<u64>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                _ => a.cmp(b),
+            };
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+
+        self.pre.len().cmp(&other.pre.len())
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            write!(f, "-{}", self.pre.join("."))?;
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,21 @@
 use std::{fmt, io};
 
+use memchr::memchr;
+
+use crate::budget::Budget;
+
+/// Writer that can also write bytes that are not valid utf-8.
+pub trait ByteWrite: fmt::Write {
+    fn write_bytes(&mut self, data: &[u8]) -> fmt::Result;
+}
+
+impl ByteWrite for String {
+    fn write_bytes(&mut self, data: &[u8]) -> fmt::Result {
+        self.push_str(&String::from_utf8_lossy(data));
+        Ok(())
+    }
+}
+
 pub struct ToFmtWrite<T>(pub T)
 where
     T: io::Write;
@@ -9,7 +25,16 @@ where
     T: io::Write,
 {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.0.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+        self.write_bytes(s.as_bytes())
+    }
+}
+
+impl<T> ByteWrite for ToFmtWrite<T>
+where
+    T: io::Write,
+{
+    fn write_bytes(&mut self, data: &[u8]) -> fmt::Result {
+        self.0.write_all(data).map_err(|_| fmt::Error)
     }
 }
 
@@ -20,3 +45,63 @@ impl fmt::Write for FakeWriter {
         Ok(())
     }
 }
+
+impl ByteWrite for FakeWriter {
+    fn write_bytes(&mut self, _data: &[u8]) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// Writer that counts the written bytes to the budget. It fails with
+/// `fmt::Error` when the output limit is exceeded.
+pub struct BudgetWriter<'a> {
+    pub inner: &'a mut dyn ByteWrite,
+    pub budget: &'a Budget,
+}
+
+impl fmt::Write for BudgetWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes())
+    }
+}
+
+impl ByteWrite for BudgetWriter<'_> {
+    fn write_bytes(&mut self, data: &[u8]) -> fmt::Result {
+        // the error is recovered from the budget by the caller
+        self.budget.add_output(data.len()).map_err(|_| fmt::Error)?;
+        self.inner.write_bytes(data)
+    }
+}
+
+/// Writer that writes `\n` as `\r\n` unless it already is preceded by `\r`.
+pub struct CrlfWriter<'a> {
+    pub inner: &'a mut dyn ByteWrite,
+    pub cr: bool,
+}
+
+impl fmt::Write for CrlfWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes())
+    }
+}
+
+impl ByteWrite for CrlfWriter<'_> {
+    fn write_bytes(&mut self, data: &[u8]) -> fmt::Result {
+        let mut rest = data;
+        while let Some(i) = memchr(b'\n', rest) {
+            let cr = if i == 0 {
+                self.cr
+            } else {
+                rest[i - 1] == b'\r'
+            };
+            self.inner.write_bytes(&rest[..i])?;
+            self.inner.write_str(if cr { "\n" } else { "\r\n" })?;
+            self.cr = false;
+            rest = &rest[i + 1..];
+        }
+        if let Some(&c) = rest.last() {
+            self.cr = c == b'\r';
+        }
+        self.inner.write_bytes(rest)
+    }
+}